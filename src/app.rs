@@ -1,28 +1,85 @@
 
-use std::{borrow::Borrow, sync::{Arc, Mutex, MutexGuard}};
+use std::sync::{Arc, Mutex};
 
 use wasm_bindgen::prelude::*;
-use winit::{application::ApplicationHandler, event::WindowEvent, event_loop::{self, ControlFlow, EventLoop, EventLoopProxy}, window::{Window, WindowId}};
+use winit::{application::ApplicationHandler, event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent}, event_loop::{ControlFlow, EventLoop, EventLoopProxy}, window::{Window, WindowId}};
 
 #[path = "./types.rs"] pub mod types;
-use crate::gfx::gfx_state::GfxState;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{hot_reload::HotReload, shader_files::ShaderFiles};
+use crate::error::Error;
+use crate::gfx::{buffer_pass::PassTarget, channels::{ChannelSamplerDesc, MAX_CHANNELS}, context::SharedContext, export::{self, ExportDesc, ExportFormat}, gfx_state::GfxState, mesh::MeshData, pipeline::DrawDesc, pipeline_build::PipelineBuild, shader::{EntryPoints, ShaderLanguage}};
 
 enum UserEvents {
-  CreateInstance((String, Option<js_sys::Function>)),
+  CreateInstance((String, Option<js_sys::Function>, js_sys::Function, js_sys::Function)),
   DestroyInstance(WindowId),
-  UpdateShader((String, js_sys::Function, WindowId)),
-  CompileShader((String, js_sys::Function, WindowId)),
+  UpdateShader((String, ShaderLanguage, PassTarget, EntryPoints, js_sys::Function, Option<js_sys::Function>, WindowId)),
+  RemovePass((PassTarget, WindowId)),
+  SetChannel((usize, Vec<u8>, ChannelSamplerDesc, js_sys::Function, js_sys::Function, WindowId)),
+  ClearChannel((usize, WindowId)),
+  SetStorageBuffer((usize, Option<u64>, WindowId)),
+  SetStorageTexture((usize, Option<(u32, u32)>, WindowId)),
+  SetUniform((String, Vec<f64>, WindowId)),
+  SetOverride((String, f64, js_sys::Function, js_sys::Function, WindowId)),
+  SetDraw((DrawDesc, js_sys::Function, js_sys::Function, WindowId)),
+  SetMesh((Option<Vec<u8>>, js_sys::Function, js_sys::Function, WindowId)),
+  CaptureFrame((js_sys::Function, js_sys::Function, WindowId)),
+  CompileShader((String, ShaderLanguage, PassTarget, EntryPoints, js_sys::Function, WindowId)),
+  Pause(WindowId),
+  Resume(WindowId),
+  Seek((f32, WindowId)),
+  SetSpeed((f32, WindowId)),
+  StepFrame((Option<f32>, WindowId)),
+  SetFixedFps((Option<f32>, WindowId)),
+  Restart(WindowId),
+  SetLoop((Option<(f32, f32)>, WindowId)),
+  GetPlaybackState((js_sys::Function, js_sys::Function, WindowId)),
+  GetCapabilities((js_sys::Function, js_sys::Function, WindowId)),
+  Export((ExportDesc, ExportFormat, js_sys::Function, Option<js_sys::Function>, js_sys::Function, js_sys::Function, WindowId)),
+  ExportGif((ExportDesc, Option<js_sys::Function>, js_sys::Function, js_sys::Function, WindowId)),
+  CancelExport(WindowId),
+}
+
+// pixel scroll deltas (touchpads, the web) per line of a mouse wheel
+const PIXELS_PER_LINE: f32 = 40.0;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct MouseState {
+  cursor: (f32, f32),
+  position: (f32, f32),
+  click_position: (f32, f32),
+  pressed: bool,
+  clicked: bool,
 }
 
 struct AppInstance {
   window: Arc<Window>,
   gfx: GfxState,
-  pub handle: types::InstanceHandle,
+  mouse: MouseState,
+  // pipeline builds run one at a time and in order, without the instance locked while they
+  // build
+  builds: Arc<futures_util::lock::Mutex<()>>,
+}
+
+impl MouseState {
+  // matches shadertoy's iMouse: xy is the last position while the button was down,
+  // zw is the click position, z is negative when released and w is negative after the click frame
+  fn as_uniform(&self) -> [f32; 4] {
+    let z = if self.pressed { self.click_position.0 } else { -self.click_position.0 };
+    let w = if self.clicked { self.click_position.1 } else { -self.click_position.1 };
+    [self.position.0, self.position.1, z, w]
+  }
 }
 
 #[derive(Default)]
 struct App {
   instances: Arc<Mutex<Vec<Arc<Mutex<AppInstance>>>>>,
+  // device every instance renders with, created with the first one
+  context: Arc<Mutex<SharedContext>>,
+  #[cfg(not(target_arch = "wasm32"))]
+  files: ShaderFiles,
+  #[cfg(not(target_arch = "wasm32"))]
+  hot_reload: Option<HotReload>,
 }
 
 #[wasm_bindgen]
@@ -30,30 +87,226 @@ pub struct EventHandler {
   event_loop: EventLoopProxy<UserEvents>,
 }
 
+// logs the error and hands it to js if anyone is listening
+fn report_error(on_error: Option<&js_sys::Function>, err: Error) {
+  log::error!("[app] {}", err);
+  if let Some(on_error) = on_error {
+    let _ = on_error.call1(&JsValue::NULL, &JsValue::from(err));
+  }
+}
+
 impl AppInstance {
-  async fn update_shader(instance: Arc<Mutex<AppInstance>>, shader_source: String, callback: js_sys::Function) {
+  // starts a build under the instance lock and installs it under the lock again, `finish` reads
+  // what the build changed before another one can
+  async fn build<T>(
+    instance: &Arc<Mutex<AppInstance>>,
+    begin: impl FnOnce(&GfxState) -> Result<PipelineBuild, Error>,
+    finish: impl FnOnce(&GfxState) -> T,
+  ) -> Result<T, Error> {
+    let builds = instance.lock().expect("[app] failed to lock instance").builds.clone();
+    let _build = builds.lock().await;
+
+    let build = begin(&instance.lock().expect("[app] failed to lock instance").gfx)?;
+    let built = build.run().await?;
+
     let mut instance = instance.lock().expect("[app] failed to lock instance");
+    instance.gfx.finish_build(built);
+    Ok(finish(&instance.gfx))
+  }
 
-    let result = instance.gfx.compile_shader(&shader_source).await;
-    if result.messages.is_empty() {
-      instance.gfx.update_shader(&shader_source);
-    }
+  async fn update_shader(
+    instance: Arc<Mutex<AppInstance>>,
+    shader_source: String,
+    language: ShaderLanguage,
+    pass: PassTarget,
+    entry_points: EntryPoints,
+    callback: js_sys::Function,
+    on_error: Option<js_sys::Function>,
+  ) {
+    let result = Self::build(
+      &instance,
+      |gfx| Ok(gfx.begin_update_shader(pass, &shader_source, language, &entry_points)),
+      |gfx| (
+        gfx.uniform_layout(pass).map(|layout| layout.fields.clone()).unwrap_or_default(),
+        gfx.parameters(pass).to_vec(),
+      ),
+    ).await;
+
+    let (messages, uniforms, parameters) = match result {
+      Ok((uniforms, parameters)) => (Vec::new(), uniforms, parameters),
+      // the previous pipeline is kept, report why the new one was rejected
+      Err(Error::Pipeline(messages)) => (messages, Vec::new(), Vec::new()),
+      Err(err) => {
+        report_error(on_error.as_ref(), err);
+        (Vec::new(), Vec::new(), Vec::new())
+      },
+    };
 
-    let result: types::ShaderCompilationInfo = result.into();
+    let result = types::ShaderCompilationInfo::from(wgpu::CompilationInfo { messages })
+      .with_uniforms(uniforms)
+      .with_parameters(parameters);
     let _ = callback.call1(&JsValue::NULL, &JsValue::from(result));
   }
 
-  async fn compile_shader(instance: Arc<Mutex<AppInstance>>, shader_source: String, callback: js_sys::Function) {
-    let instance = instance.lock().expect("[app] failed to lock instance");
+  async fn set_override(instance: Arc<Mutex<AppInstance>>, name: String, value: f64, resolve: js_sys::Function, reject: js_sys::Function) {
+    match Self::build(&instance, |gfx| gfx.begin_set_override(&name, value), |_| ()).await {
+      Ok(()) => {
+        let _ = resolve.call0(&JsValue::NULL);
+      },
+      Err(err) => report_error(Some(&reject), err),
+    }
+  }
+
+  async fn set_draw(instance: Arc<Mutex<AppInstance>>, draw: DrawDesc, resolve: js_sys::Function, reject: js_sys::Function) {
+    match Self::build(&instance, |gfx| gfx.begin_set_draw(draw), |_| ()).await {
+      Ok(()) => {
+        let _ = resolve.call0(&JsValue::NULL);
+      },
+      Err(err) => report_error(Some(&reject), err),
+    }
+  }
+
+  async fn set_mesh(instance: Arc<Mutex<AppInstance>>, data: Option<Vec<u8>>, resolve: js_sys::Function, reject: js_sys::Function) {
+    let mesh = match data.as_deref().map(MeshData::from_bytes).transpose() {
+      Ok(mesh) => mesh,
+      Err(err) => {
+        report_error(Some(&reject), err);
+        return;
+      },
+    };
 
-    let result = instance.gfx.compile_shader(&shader_source).await;
-    let result: types::ShaderCompilationInfo = result.into();
+    match Self::build(&instance, |gfx| gfx.begin_set_mesh(mesh.as_ref()), |_| ()).await {
+      Ok(()) => {
+        let _ = resolve.call0(&JsValue::NULL);
+      },
+      Err(err) => report_error(Some(&reject), err),
+    }
+  }
+
+  // waits for the builds started before it, a build in flight would bring the pass back
+  async fn remove_pass(instance: Arc<Mutex<AppInstance>>, pass: PassTarget) {
+    let builds = instance.lock().expect("[app] failed to lock instance").builds.clone();
+    let _build = builds.lock().await;
+    instance.lock().expect("[app] failed to lock instance").gfx.remove_pass(pass);
+  }
+
+  // nothing is installed, so this doesn't wait for other builds
+  async fn compile_shader(
+    instance: Arc<Mutex<AppInstance>>,
+    shader_source: String,
+    language: ShaderLanguage,
+    pass: PassTarget,
+    entry_points: EntryPoints,
+    callback: js_sys::Function,
+  ) {
+    let build = instance.lock().expect("[app] failed to lock instance").gfx.begin_compile_shader(pass, &shader_source, language, &entry_points);
+
+    let (info, parameters) = build.check().await;
+    let result = types::ShaderCompilationInfo::from(info).with_parameters(parameters);
     let _ = callback.call1(&JsValue::NULL, &JsValue::from(result));
   }
 
+  async fn capture_frame(instance: Arc<Mutex<AppInstance>>, resolve: js_sys::Function, reject: js_sys::Function) {
+    // only hold the lock while recording, the readback can take a few frames
+    let capture = {
+      let instance = instance.lock().expect("[app] failed to lock instance");
+      instance.gfx.begin_capture()
+    };
+
+    match capture.into_png().await {
+      Ok(png) => {
+        let _ = resolve.call1(&JsValue::NULL, &js_sys::Uint8Array::from(png.as_slice()));
+      },
+      Err(err) => report_error(Some(&reject), err.into()),
+    }
+  }
+
+  // the lock is only held while a frame is recorded, so the page stays responsive
+  async fn export(
+    instance: Arc<Mutex<AppInstance>>,
+    desc: ExportDesc,
+    format: ExportFormat,
+    on_frame: js_sys::Function,
+    on_progress: Option<js_sys::Function>,
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+  ) {
+    if let Err(err) = instance.lock().expect("[app] failed to lock instance").gfx.begin_export(desc) {
+      report_error(Some(&reject), err);
+      return;
+    }
+
+    let result = export::run(
+      &desc,
+      format,
+      |index| instance.lock().expect("[app] failed to lock instance").gfx.render_export_frame(index),
+      |frame| {
+        let _ = on_frame.call1(&JsValue::NULL, &types::export_frame_to_js_value(&frame));
+        if let Some(on_progress) = on_progress.as_ref() {
+          let _ = on_progress.call2(&JsValue::NULL, &JsValue::from_f64((frame.index + 1) as f64), &JsValue::from_f64(frame.frame_count as f64));
+        }
+        Ok(())
+      },
+    ).await;
+
+    instance.lock().expect("[app] failed to lock instance").gfx.end_export();
+
+    match result {
+      Ok(frame_count) => {
+        let _ = resolve.call1(&JsValue::NULL, &JsValue::from_f64(frame_count as f64));
+      },
+      Err(err) => report_error(Some(&reject), err),
+    }
+  }
+
+  async fn export_gif(
+    instance: Arc<Mutex<AppInstance>>,
+    desc: ExportDesc,
+    on_progress: Option<js_sys::Function>,
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+  ) {
+    let desc = {
+      let mut instance = instance.lock().expect("[app] failed to lock instance");
+      let size = desc.size.unwrap_or_else(|| export::fit_size(instance.gfx.render_size(), export::GIF_MAX_SIZE));
+      let desc = ExportDesc { size: Some(size), ..desc }.looped();
+      if let Err(err) = instance.gfx.begin_export(desc) {
+        report_error(Some(&reject), err);
+        return;
+      }
+      desc
+    };
+
+    let result = export::encode_gif(
+      &desc,
+      |index| instance.lock().expect("[app] failed to lock instance").gfx.render_export_frame(index),
+      |done, total| {
+        if let Some(on_progress) = on_progress.as_ref() {
+          let _ = on_progress.call2(&JsValue::NULL, &JsValue::from_f64(done as f64), &JsValue::from_f64(total as f64));
+        }
+      },
+    ).await;
+
+    instance.lock().expect("[app] failed to lock instance").gfx.end_export();
+
+    match result {
+      Ok(bytes) => {
+        let _ = resolve.call1(&JsValue::NULL, &js_sys::Uint8Array::from(bytes.as_slice()));
+      },
+      Err(err) => report_error(Some(&reject), err),
+    }
+  }
+
   #[cfg(not(target_arch = "wasm32"))]
-  async fn create_instance(window: Arc<Window>, instances: Arc<Mutex<Vec<Arc<Mutex<AppInstance>>>>>) {
-    let gfx = GfxState::new(window.clone()).await;
+  async fn create_instance(
+    window: Arc<Window>,
+    instances: Arc<Mutex<Vec<Arc<Mutex<AppInstance>>>>>,
+    context: Arc<Mutex<SharedContext>>,
+    files: &ShaderFiles,
+  ) -> Result<types::InstanceHandle, Error> {
+    let (context, surface) = SharedContext::get_or_create(&context, window.clone()).await?;
+    let mut gfx = GfxState::new(context, window.clone(), surface)?;
+    files.load(&mut gfx).await;
 
     let mut instances = instances.lock().expect("[app] failed to lock instances");
 
@@ -64,36 +317,45 @@ impl AppInstance {
     instances.push(Arc::new(Mutex::new(AppInstance {
       window: window.clone(),
       gfx,
-      handle,
+      mouse: MouseState::default(),
+      builds: Default::default(),
     })));
+
+    Ok(handle)
   }
 
   #[cfg(target_arch = "wasm32")]
-  async fn create_instance(
-    window: Arc<Window>,
-    instances: Arc<Mutex<Vec<Arc<Mutex<AppInstance>>>>>,
-    container_id: String,
-    callback: Option<js_sys::Function>,
-  ) {
-    use std::rc::Rc;
-
+  fn append_canvas(window: &Window, container_id: &str) -> Result<(), Error> {
     use winit::platform::web::WindowExtWebSys;
-    use winit::platform::web::EventLoopExtWebSys;
 
-    web_sys::window()
+    let dst = web_sys::window()
       .and_then(|win| win.document())
-      .and_then(|doc| {
-        let dst = doc.get_element_by_id(&container_id).expect("[app] failed to get canvas container");
-        let canvas = web_sys::Element::from(window.canvas()?);
+      .and_then(|doc| doc.get_element_by_id(container_id))
+      .ok_or_else(|| Error::ContainerNotFound(container_id.to_string()))?;
+
+    window.canvas()
+      .and_then(|canvas| {
+        let canvas = web_sys::Element::from(canvas);
         canvas.set_attribute("width", "100%").ok()?;
         canvas.set_attribute("height", "100%").ok()?;
         canvas.set_attribute("style", "width: 100%; height: 100%;").ok()?;
         dst.append_child(&canvas).ok()?;
         Some(())
       })
-    .expect("[app] failed to append canvas");
+      .ok_or_else(|| Error::InvalidParameter(format!("failed to append canvas to `{}`", container_id)))
+  }
 
-    let gfx = GfxState::new(window.clone()).await;
+  #[cfg(target_arch = "wasm32")]
+  async fn create_instance(
+    window: Arc<Window>,
+    instances: Arc<Mutex<Vec<Arc<Mutex<AppInstance>>>>>,
+    context: Arc<Mutex<SharedContext>>,
+    container_id: String,
+  ) -> Result<types::InstanceHandle, Error> {
+    Self::append_canvas(&window, &container_id)?;
+
+    let context = SharedContext::get_or_create(&context).await?;
+    let gfx = GfxState::new(context, window.clone())?;
     let mut instances = instances.lock().expect("[app] failed to lock instances");
 
     let handle = types::InstanceHandle {
@@ -103,23 +365,84 @@ impl AppInstance {
     instances.push(Arc::new(Mutex::new(AppInstance {
       window: window.clone(),
       gfx,
-      handle,
+      mouse: MouseState::default(),
+      builds: Default::default(),
     })));
 
-    if let Some(callback) = callback {
-      let _ = callback.call1(&JsValue::NULL, &JsValue::from(handle));
+    Ok(handle)
+  }
+}
+
+impl App {
+  fn find_instance(&self, window_id: WindowId) -> Option<Arc<Mutex<AppInstance>>> {
+    let instances = self.instances.lock().expect("[app] failed to lock instances");
+    instances.iter()
+      .find(|instance| instance.lock().expect("[app] failed to lock instance").window.id() == window_id)
+      .cloned()
+  }
+
+  // runs `f` on the instance's gfx state, errors are logged
+  fn with_gfx(&self, window_id: WindowId, f: impl FnOnce(&mut GfxState) -> Result<(), Error>) {
+    let result = self.find_instance(window_id)
+      .ok_or(Error::InstanceNotFound)
+      .and_then(|instance| f(&mut instance.lock().expect("[app] failed to lock instance").gfx));
+
+    if let Err(err) = result {
+      report_error(None, err);
     }
   }
 }
 
 impl ApplicationHandler<UserEvents> for App {
+  #[cfg(not(target_arch = "wasm32"))]
+  fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+    let changed = match self.hot_reload.as_ref() {
+      Some(hot_reload) => hot_reload.changed_files(),
+      None => return,
+    };
+
+    if changed.is_empty() {
+      return;
+    }
+
+    let dependencies = self.files.dependencies();
+    if !changed.iter().any(|path| dependencies.contains(path)) {
+      return;
+    }
+
+    log::warn!("[app] event: files_changed: {:?}", changed);
+    let instances = self.instances.lock().expect("[app] failed to lock instances");
+
+    for instance in instances.iter() {
+      let mut instance = instance.lock().expect("[app] failed to lock instance");
+      if pollster::block_on(self.files.load_passes(&mut instance.gfx)) {
+        log::info!("[app] reloaded shaders");
+      }
+    }
+
+    // includes may have changed
+    if let Some(hot_reload) = self.hot_reload.as_mut() {
+      hot_reload.watch(&dependencies);
+    }
+  }
+
   fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
     log::warn!("[app] event: resumed");
 
-    let window = Arc::new(event_loop.create_window(Window::default_attributes()).expect("[app] failed to create window"));
+    let window = match event_loop.create_window(Window::default_attributes()) {
+      Ok(window) => Arc::new(window),
+      Err(err) => {
+        log::error!("[app] {}", Error::from(err));
+        event_loop.exit();
+        return;
+      },
+    };
 
     #[cfg(not(target_arch = "wasm32"))]
-    pollster::block_on(AppInstance::create_instance(window.clone(), self.instances.clone()));
+    if let Err(err) = pollster::block_on(AppInstance::create_instance(window.clone(), self.instances.clone(), self.context.clone(), &self.files)) {
+      eprintln!("error: {}", err);
+      event_loop.exit();
+    }
   }
 
   fn window_event(
@@ -146,14 +469,67 @@ impl ApplicationHandler<UserEvents> for App {
       },
       WindowEvent::RedrawRequested => {
         instance.window.request_redraw();
+        let mouse = instance.mouse.as_uniform();
+        instance.gfx.set_mouse(mouse);
         let _ = instance.gfx.render();
+        instance.mouse.clicked = false;
       },
       WindowEvent::Resized(size) => {
         log::warn!("[app] event: resized: {:?}", size);
         instance.gfx.resize(size);
       },
+      WindowEvent::CursorMoved { position, .. } => {
+        // shaders expect the origin at the bottom left corner
+        let height = instance.window.inner_size().height as f32;
+        let cursor = (position.x as f32, height - position.y as f32);
+        // dragging orbits the camera around a loaded mesh
+        if instance.mouse.pressed && instance.gfx.has_mesh() {
+          let (dx, dy) = (cursor.0 - instance.mouse.cursor.0, cursor.1 - instance.mouse.cursor.1);
+          instance.gfx.camera_mut().rotate(dx, dy);
+        }
+        instance.mouse.cursor = cursor;
+        if instance.mouse.pressed {
+          instance.mouse.position = instance.mouse.cursor;
+        }
+      },
+      WindowEvent::MouseWheel { delta, .. } if instance.gfx.has_mesh() => {
+        let lines = match delta {
+          MouseScrollDelta::LineDelta(_, y) => y,
+          MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+        };
+        instance.gfx.camera_mut().zoom(lines);
+      },
+      // space toggles pause, the arrow keys step a frame
+      #[cfg(not(target_arch = "wasm32"))]
+      WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+        use winit::keyboard::{Key, NamedKey};
+
+        let playback = instance.gfx.playback_mut();
+        let _ = match event.logical_key {
+          Key::Named(NamedKey::Space) => {
+            playback.paused = !playback.paused;
+            Ok(())
+          },
+          Key::Named(NamedKey::ArrowRight) => playback.step(None),
+          Key::Named(NamedKey::ArrowLeft) => playback.step(playback.fixed_timestep.or(Some(1.0 / 60.0)).map(|delta_time| -delta_time)),
+          _ => Ok(()),
+        };
+      },
       WindowEvent::MouseInput { device_id, state, button } => {
         log::warn!("[app] event: mouse_input: {:?}, {:?}, {:?}", device_id, state, button);
+        if button == MouseButton::Left {
+          match state {
+            ElementState::Pressed => {
+              instance.mouse.pressed = true;
+              instance.mouse.clicked = true;
+              instance.mouse.position = instance.mouse.cursor;
+              instance.mouse.click_position = instance.mouse.cursor;
+            },
+            ElementState::Released => {
+              instance.mouse.pressed = false;
+            },
+          }
+        }
       },
       _ => {},
     }
@@ -161,47 +537,224 @@ impl ApplicationHandler<UserEvents> for App {
 
   fn user_event(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, event: UserEvents) {
     match event {
-      UserEvents::CreateInstance((container_id, callback)) => {
+      UserEvents::CreateInstance((container_id, callback, resolve, reject)) => {
         log::warn!("[app] event: create_instance: {}", container_id);
-        
-        let window = Arc::new(event_loop.create_window(Window::default_attributes()).expect("[app] failed to create window"));
+
+        let window = match event_loop.create_window(Window::default_attributes()) {
+          Ok(window) => Arc::new(window),
+          Err(err) => {
+            report_error(Some(&reject), err.into());
+            return;
+          },
+        };
 
         #[cfg(not(target_arch = "wasm32"))]
-        pollster::block_on(AppInstance::create_instance(window.clone(), self.instances.clone()));
+        match pollster::block_on(AppInstance::create_instance(window.clone(), self.instances.clone(), self.context.clone(), &self.files)) {
+          Ok(handle) => {
+            if let Some(callback) = callback {
+              let _ = callback.call1(&JsValue::NULL, &JsValue::from(handle));
+            }
+            let _ = resolve.call1(&JsValue::NULL, &JsValue::from(handle));
+          },
+          Err(err) => report_error(Some(&reject), err),
+        }
 
         #[cfg(target_arch = "wasm32")]
-        wasm_bindgen_futures::spawn_local(AppInstance::create_instance(window.clone(), self.instances.clone(), container_id, callback));
-      },
-      UserEvents::UpdateShader((shader_source, callback, window_id)) => {
-        log::warn!("[app] event: update_shader: {:?}", window_id);
-        let instances = self.instances.lock().expect("[app] failed to lock instances");
-
-        for instance in instances.iter() {
-          {
-            let instance = instance.lock().expect("[app] failed to lock instance");
-            if instance.window.id() != window_id {
-              continue;
+        {
+          let instances = self.instances.clone();
+          let context = self.context.clone();
+          wasm_bindgen_futures::spawn_local(async move {
+            match AppInstance::create_instance(window, instances, context, container_id).await {
+              Ok(handle) => {
+                if let Some(callback) = callback {
+                  let _ = callback.call1(&JsValue::NULL, &JsValue::from(handle));
+                }
+                let _ = resolve.call1(&JsValue::NULL, &JsValue::from(handle));
+              },
+              Err(err) => report_error(Some(&reject), err),
             }
-          }
+          });
+        }
+      },
+      UserEvents::UpdateShader((shader_source, language, pass, entry_points, callback, on_error, window_id)) => {
+        log::warn!("[app] event: update_shader: {:?}, {:?}", window_id, pass);
 
-          wasm_bindgen_futures::spawn_local(AppInstance::update_shader(instance.clone(), shader_source.clone(), callback.clone()));
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::update_shader(instance, shader_source, language, pass, entry_points, callback, on_error)),
+          None => report_error(on_error.as_ref(), Error::InstanceNotFound),
         }
       },
-      UserEvents::CompileShader((shader_source, callback, window_id)) => {
+      UserEvents::CompileShader((shader_source, language, pass, entry_points, callback, window_id)) => {
         log::warn!("[app] event: compile_shader: {:?}", window_id);
-        let instances = self.instances.lock().expect("[app] failed to lock instances");
 
-        for instance in instances.iter() {
-          {
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::compile_shader(instance, shader_source, language, pass, entry_points, callback)),
+          None => report_error(None, Error::InstanceNotFound),
+        }
+      },
+      UserEvents::RemovePass((pass, window_id)) => {
+        log::warn!("[app] event: remove_pass: {:?}, {:?}", window_id, pass);
+
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::remove_pass(instance, pass)),
+          None => report_error(None, Error::InstanceNotFound),
+        }
+      },
+      UserEvents::SetChannel((index, data, sampler, resolve, reject, window_id)) => {
+        log::warn!("[app] event: set_channel: {:?}, {}", window_id, index);
+
+        let result = self.find_instance(window_id)
+          .ok_or(Error::InstanceNotFound)
+          .and_then(|instance| instance.lock().expect("[app] failed to lock instance").gfx.set_channel(index, &data, sampler));
+
+        match result {
+          Ok(()) => {
+            let _ = resolve.call0(&JsValue::NULL);
+          },
+          Err(err) => report_error(Some(&reject), err),
+        }
+      },
+      UserEvents::ClearChannel((index, window_id)) => {
+        log::warn!("[app] event: clear_channel: {:?}, {}", window_id, index);
+
+        self.with_gfx(window_id, |gfx| gfx.clear_channel(index));
+      },
+      UserEvents::SetStorageBuffer((index, size, window_id)) => {
+        log::warn!("[app] event: set_storage_buffer: {:?}, {}, {:?}", window_id, index, size);
+        self.with_gfx(window_id, |gfx| gfx.set_storage_buffer_size(index, size));
+      },
+      UserEvents::SetStorageTexture((index, size, window_id)) => {
+        log::warn!("[app] event: set_storage_texture: {:?}, {}, {:?}", window_id, index, size);
+        self.with_gfx(window_id, |gfx| gfx.set_storage_texture_size(index, size));
+      },
+      UserEvents::SetUniform((name, values, window_id)) => {
+        let result = self.find_instance(window_id)
+          .ok_or(Error::InstanceNotFound)
+          .and_then(|instance| instance.lock().expect("[app] failed to lock instance").gfx.set_uniform(&name, &values));
+
+        if let Err(err) = result {
+          report_error(None, err);
+        }
+      },
+      UserEvents::SetOverride((name, value, resolve, reject, window_id)) => {
+        log::warn!("[app] event: set_override: {:?}, {}", window_id, name);
+
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::set_override(instance, name, value, resolve, reject)),
+          None => report_error(Some(&reject), Error::InstanceNotFound),
+        }
+      },
+      UserEvents::SetDraw((draw, resolve, reject, window_id)) => {
+        log::warn!("[app] event: set_draw: {:?}, {:?}", window_id, draw);
+
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::set_draw(instance, draw, resolve, reject)),
+          None => report_error(Some(&reject), Error::InstanceNotFound),
+        }
+      },
+      UserEvents::SetMesh((data, resolve, reject, window_id)) => {
+        log::warn!("[app] event: set_mesh: {:?}, {:?} bytes", window_id, data.as_ref().map(Vec::len));
+
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::set_mesh(instance, data, resolve, reject)),
+          None => report_error(Some(&reject), Error::InstanceNotFound),
+        }
+      },
+      UserEvents::CaptureFrame((resolve, reject, window_id)) => {
+        log::warn!("[app] event: capture_frame: {:?}", window_id);
+
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::capture_frame(instance, resolve, reject)),
+          None => report_error(Some(&reject), Error::InstanceNotFound),
+        }
+      },
+      UserEvents::Pause(window_id) => {
+        log::warn!("[app] event: pause: {:?}", window_id);
+        self.with_gfx(window_id, |gfx| {
+          gfx.playback_mut().paused = true;
+          Ok(())
+        });
+      },
+      UserEvents::Resume(window_id) => {
+        log::warn!("[app] event: resume: {:?}", window_id);
+        self.with_gfx(window_id, |gfx| {
+          gfx.playback_mut().paused = false;
+          Ok(())
+        });
+      },
+      UserEvents::Seek((time, window_id)) => {
+        log::warn!("[app] event: seek: {:?}, {:?}", window_id, time);
+        self.with_gfx(window_id, |gfx| {
+          gfx.set_time(time);
+          Ok(())
+        });
+      },
+      UserEvents::SetSpeed((speed, window_id)) => {
+        log::warn!("[app] event: set_speed: {:?}, {:?}", window_id, speed);
+        self.with_gfx(window_id, |gfx| gfx.playback_mut().set_speed(speed));
+      },
+      UserEvents::StepFrame((delta_time, window_id)) => {
+        log::warn!("[app] event: step_frame: {:?}, {:?}", window_id, delta_time);
+        self.with_gfx(window_id, |gfx| gfx.playback_mut().step(delta_time));
+      },
+      UserEvents::SetFixedFps((fps, window_id)) => {
+        log::warn!("[app] event: set_fixed_fps: {:?}, {:?}", window_id, fps);
+        self.with_gfx(window_id, |gfx| gfx.set_fixed_fps(fps));
+      },
+      UserEvents::Restart(window_id) => {
+        log::warn!("[app] event: restart: {:?}", window_id);
+        self.with_gfx(window_id, |gfx| {
+          gfx.restart();
+          Ok(())
+        });
+      },
+      UserEvents::SetLoop((loop_range, window_id)) => {
+        log::warn!("[app] event: set_loop: {:?}, {:?}", window_id, loop_range);
+        self.with_gfx(window_id, |gfx| gfx.playback_mut().set_loop(loop_range));
+      },
+      UserEvents::GetPlaybackState((resolve, reject, window_id)) => {
+        match self.find_instance(window_id) {
+          Some(instance) => {
             let instance = instance.lock().expect("[app] failed to lock instance");
-            if instance.window.id() != window_id {
-              continue;
-            }
-          }
+            let state = types::playback_state_to_js_value(instance.gfx.time(), instance.gfx.frame(), instance.gfx.playback());
+            let _ = resolve.call1(&JsValue::NULL, &state);
+          },
+          None => report_error(Some(&reject), Error::InstanceNotFound),
+        }
+      },
+      UserEvents::GetCapabilities((resolve, reject, window_id)) => {
+        match self.find_instance(window_id) {
+          Some(instance) => {
+            let instance = instance.lock().expect("[app] failed to lock instance");
+            let capabilities = types::capabilities_to_js_value(instance.gfx.backend(), instance.gfx.supports_compute(), instance.gfx.limits());
+            let _ = resolve.call1(&JsValue::NULL, &capabilities);
+          },
+          None => report_error(Some(&reject), Error::InstanceNotFound),
+        }
+      },
+      UserEvents::Export((desc, format, on_frame, on_progress, resolve, reject, window_id)) => {
+        log::warn!("[app] event: export: {:?}, {:?}", window_id, desc);
+
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::export(instance, desc, format, on_frame, on_progress, resolve, reject)),
+          None => report_error(Some(&reject), Error::InstanceNotFound),
+        }
+      },
+      UserEvents::ExportGif((desc, on_progress, resolve, reject, window_id)) => {
+        log::warn!("[app] event: export_gif: {:?}, {:?}", window_id, desc);
 
-          wasm_bindgen_futures::spawn_local(AppInstance::compile_shader(instance.clone(), shader_source.clone(), callback.clone()));
+        match self.find_instance(window_id) {
+          Some(instance) => wasm_bindgen_futures::spawn_local(AppInstance::export_gif(instance, desc, on_progress, resolve, reject)),
+          None => report_error(Some(&reject), Error::InstanceNotFound),
         }
       },
+      UserEvents::CancelExport(window_id) => {
+        log::warn!("[app] event: cancel_export: {:?}", window_id);
+        self.with_gfx(window_id, |gfx| {
+          gfx.cancel_export();
+          Ok(())
+        });
+      },
       UserEvents::DestroyInstance(window_id) => {
         log::warn!("[app] event: destroy_instance: {:?}", window_id);
         let mut instances = self.instances.lock().expect("[app] failed to lock instances");
@@ -214,20 +767,43 @@ impl ApplicationHandler<UserEvents> for App {
   }
 }
 
-#[wasm_bindgen]
+#[cfg(not(target_arch = "wasm32"))]
 impl EventHandler {
-  #[cfg(not(target_arch = "wasm32"))]
-  pub fn new() -> Self {
+  // runs the event loop with a window showing `files`
+  pub fn run(files: ShaderFiles) -> Self {
     let event_loop = EventLoop::<UserEvents>::with_user_event().build().expect("[app] failed to create event loop");
     event_loop.set_control_flow(ControlFlow::Poll);
     let event_loop_proxy = event_loop.create_proxy();
 
-    let mut app = App::default();
+    let hot_reload = match HotReload::new() {
+      Ok(mut hot_reload) => {
+        hot_reload.watch(&files.dependencies());
+        Some(hot_reload)
+      },
+      Err(err) => {
+        log::error!("[app] failed to start file watcher: {}", err);
+        None
+      },
+    };
+
+    let mut app = App {
+      files,
+      hot_reload,
+      ..Default::default()
+    };
     event_loop.run_app(&mut app).expect("[app] failed to run event loop");
 
-    return Self {
+    Self {
       event_loop: event_loop_proxy,
-    };
+    }
+  }
+}
+
+#[wasm_bindgen]
+impl EventHandler {
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn new() -> Self {
+    Self::run(ShaderFiles::default())
   }
 
   #[cfg(target_arch = "wasm32")]
@@ -241,46 +817,308 @@ impl EventHandler {
 
     event_loop.spawn_app(App::default());
 
-    return Self {
+    Self {
       event_loop: event_loop_proxy,
-    };
+    }
   }
 
+  // resolves with the instance handle, `callback` is still called for older callers
   #[wasm_bindgen]
-  pub fn create_instance(&self, ts_params: types::IAppParams) {
+  pub fn create_instance(&self, ts_params: types::IAppParams) -> js_sys::Promise {
     let params: JsValue = ts_params.into();
-    let mut container_id = String::from("canvas-container");
-    let mut callback: Option<js_sys::Function> = None;
 
-    if params.is_object() {
-      if js_sys::Reflect::has(&params, &JsValue::from_str("containerId")).unwrap() {
-        container_id = js_sys::Reflect::get(&params, &JsValue::from_str("containerId")).unwrap().as_string().unwrap();
-      }
+    let mut parsed = Some(if params.is_object() {
+      types::get_string(&params, "containerId")
+        .and_then(|container_id| Ok((container_id, types::get_function(&params, "callback")?)))
+    } else {
+      Ok((None, None))
+    });
 
-      if js_sys::Reflect::has(&params, &JsValue::from_str("callback")).unwrap() {
-        callback = Some(js_sys::Reflect::get(&params, &JsValue::from_str("callback")).unwrap().dyn_into().unwrap());
+    js_sys::Promise::new(&mut |resolve, reject| {
+      match parsed.take().unwrap_or(Ok((None, None))) {
+        Ok((container_id, callback)) => {
+          let container_id = container_id.unwrap_or_else(|| String::from("canvas-container"));
+          let _ = self.event_loop.send_event(UserEvents::CreateInstance((container_id, callback, resolve, reject)));
+        },
+        Err(err) => report_error(Some(&reject), err),
       }
-    }
-
-    let _ = self.event_loop.send_event(UserEvents::CreateInstance((container_id, callback)));
+    })
   }
   
+  // invalid parameters throw, failures after compilation go to `onError`
   #[wasm_bindgen]
-  pub fn update_shader(&self, handle: &types::InstanceHandle, ts_params: types::IUpdateShaderParams) {
+  pub fn update_shader(&self, handle: &types::InstanceHandle, ts_params: types::IUpdateShaderParams) -> Result<(), JsValue> {
     let params = ts_params.into();
-    let shader_source = js_sys::Reflect::get(&params, &JsValue::from_str("shaderSource")).unwrap().as_string().unwrap();
-    let callback = js_sys::Reflect::get(&params, &JsValue::from_str("callback")).unwrap().dyn_into::<js_sys::Function>().unwrap();
+    let shader_source = types::require(types::get_string(&params, "shaderSource")?, "shaderSource")?;
+    let callback = types::require(types::get_function(&params, "callback")?, "callback")?;
+    let on_error = types::get_function(&params, "onError")?;
+    let language = types::shader_language_from_js(&types::get_param(&params, "language")?.unwrap_or_default())?;
+    let pass = types::pass_target_from_js(&types::get_param(&params, "pass")?.unwrap_or_default())?;
+    let entry_points = types::entry_points_from_js(&params)?;
+
+    let _ = self.event_loop.send_event(UserEvents::UpdateShader((shader_source, language, pass, entry_points, callback, on_error, handle.window_id)));
+    Ok(())
+  }
+
+  #[wasm_bindgen]
+  pub fn remove_pass(&self, handle: &types::InstanceHandle, pass: types::TPass) -> Result<(), JsValue> {
+    let pass = types::pass_target_from_js(&pass.into())?;
+
+    let _ = self.event_loop.send_event(UserEvents::RemovePass((pass, handle.window_id)));
+    Ok(())
+  }
+
+  #[wasm_bindgen]
+  pub fn compile_shader(&self, handle: &types::InstanceHandle, ts_params: types::IUpdateShaderParams) -> Result<(), JsValue> {
+    let params = ts_params.into();
+    let shader_source = types::require(types::get_string(&params, "shaderSource")?, "shaderSource")?;
+    let callback = types::require(types::get_function(&params, "callback")?, "callback")?;
+    let language = types::shader_language_from_js(&types::get_param(&params, "language")?.unwrap_or_default())?;
+    let pass = types::pass_target_from_js(&types::get_param(&params, "pass")?.unwrap_or_default())?;
+    let entry_points = types::entry_points_from_js(&params)?;
+
+    let _ = self.event_loop.send_event(UserEvents::CompileShader((shader_source, language, pass, entry_points, callback, handle.window_id)));
+    Ok(())
+  }
+
+  // resolves once the image is uploaded, rejects if it can't be decoded
+  #[wasm_bindgen]
+  pub fn set_channel(&self, handle: &types::InstanceHandle, ts_params: types::IChannelParams) -> Result<js_sys::Promise, JsValue> {
+    let params = ts_params.into();
+    let index = channel_index(types::require(types::get_number(&params, "channel")?, "channel")?)?;
+    let data = types::require(types::get_param(&params, "data")?, "data")?
+      .dyn_into::<js_sys::Uint8Array>()
+      .map_err(|_| Error::InvalidParameter(String::from("`data` must be a Uint8Array")))?
+      .to_vec();
+    let sampler = types::channel_sampler_from_js(&params)?;
+
+    let window_id = handle.window_id;
+    let mut data = Some(data);
+    Ok(js_sys::Promise::new(&mut |resolve, reject| {
+      let data = data.take().unwrap_or_default();
+      let _ = self.event_loop.send_event(UserEvents::SetChannel((index, data, sampler, resolve, reject, window_id)));
+    }))
+  }
+
+  #[wasm_bindgen]
+  pub fn clear_channel(&self, handle: &types::InstanceHandle, channel: u32) -> Result<(), JsValue> {
+    let index = channel_index(channel as f64)?;
+
+    let _ = self.event_loop.send_event(UserEvents::ClearChannel((index, handle.window_id)));
+    Ok(())
+  }
+
+  // bytes of the storage buffer at @group(1) @binding(5 + index), left out it's as large as
+  // the shaders declare it. resizing clears the buffer, failures go to the console
+  #[wasm_bindgen]
+  pub fn set_storage_buffer(&self, handle: &types::InstanceHandle, index: u32, size: Option<f64>) -> Result<(), JsValue> {
+    let size = match size {
+      Some(size) if size.fract() != 0.0 || size < 1.0 => return Err(Error::InvalidParameter(format!("storage buffer size must be a positive integer, got {}", size)).into()),
+      size => size.map(|size| size as u64),
+    };
+
+    let _ = self.event_loop.send_event(UserEvents::SetStorageBuffer((index as usize, size, handle.window_id)));
+    Ok(())
+  }
+
+  // size of the storage texture at @group(1) @binding(9 + index), left out it follows the
+  // canvas size. resizing clears the texture
+  #[wasm_bindgen]
+  pub fn set_storage_texture(&self, handle: &types::InstanceHandle, index: u32, width: Option<u32>, height: Option<u32>) -> Result<(), JsValue> {
+    let size = match (width, height) {
+      (Some(width), Some(height)) => Some((width, height)),
+      (None, None) => None,
+      _ => return Err(Error::InvalidParameter(String::from("storage texture needs both `width` and `height`")).into()),
+    };
+
+    let _ = self.event_loop.send_event(UserEvents::SetStorageTexture((index as usize, size, handle.window_id)));
+    Ok(())
+  }
+
+  // `value` is a number, boolean or array of numbers (column major for matrices), the
+  // shader has to declare `name` in its uniform struct at @group(3) @binding(0)
+  #[wasm_bindgen]
+  pub fn set_uniform(&self, handle: &types::InstanceHandle, name: String, value: types::TUniformValue) -> Result<(), JsValue> {
+    let values = types::uniform_values_from_js(&value.into())?;
+
+    let _ = self.event_loop.send_event(UserEvents::SetUniform((name, values, handle.window_id)));
+    Ok(())
+  }
+
+  // `value` is a number or boolean for the wgsl `override` constant `name`, the passes that
+  // declare it are rebuilt. resolves once they draw with it, rejects with
+  // `PIPELINE_CREATION_FAILED` when the value doesn't fit the override's type
+  #[wasm_bindgen]
+  pub fn set_override(&self, handle: &types::InstanceHandle, name: String, value: types::TUniformValue) -> Result<js_sys::Promise, JsValue> {
+    let value = match types::uniform_values_from_js(&value.into())?.as_slice() {
+      [value] => *value,
+      _ => return Err(Error::InvalidParameter(format!("override `{}` takes a single number or boolean", name)).into()),
+    };
+
+    let window_id = handle.window_id;
+    Ok(js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::SetOverride((name.clone(), value, resolve, reject, window_id)));
+    }))
+  }
+
+  // resolves once the image pass draws with the new settings, rejects with
+  // `PIPELINE_CREATION_FAILED` when the shader can't be drawn that way
+  #[wasm_bindgen]
+  pub fn set_draw(&self, handle: &types::InstanceHandle, ts_params: types::IDrawParams) -> Result<js_sys::Promise, JsValue> {
+    let draw = types::draw_desc_from_js(&ts_params.into())?;
+
+    let window_id = handle.window_id;
+    Ok(js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::SetDraw((draw, resolve, reject, window_id)));
+    }))
+  }
+
+  // `data` is an obj or binary gltf (.glb) file, the image pass draws it with depth testing
+  // and an orbit camera instead of `vertexCount` vertices. left out the mesh is removed.
+  // rejects with `MESH_LOAD_FAILED` when the file can't be read
+  #[wasm_bindgen]
+  pub fn set_mesh(&self, handle: &types::InstanceHandle, data: Option<js_sys::Uint8Array>) -> js_sys::Promise {
+    let window_id = handle.window_id;
+    let mut data = Some(data.map(|data| data.to_vec()));
+    js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::SetMesh((data.take().flatten(), resolve, reject, window_id)));
+    })
+  }
+
+  // resolves with the last rendered frame as png bytes (Uint8Array)
+  #[wasm_bindgen]
+  pub fn capture_frame(&self, handle: &types::InstanceHandle) -> js_sys::Promise {
+    let window_id = handle.window_id;
+    js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::CaptureFrame((resolve, reject, window_id)));
+    })
+  }
+
+  // playback keeps the image pass on screen while paused, buffer passes don't advance
+  #[wasm_bindgen]
+  pub fn pause(&self, handle: &types::InstanceHandle) {
+    let _ = self.event_loop.send_event(UserEvents::Pause(handle.window_id));
+  }
+
+  #[wasm_bindgen]
+  pub fn resume(&self, handle: &types::InstanceHandle) {
+    let _ = self.event_loop.send_event(UserEvents::Resume(handle.window_id));
+  }
+
+  // jumps to `time` in seconds, the loop range applies from the next frame
+  #[wasm_bindgen]
+  pub fn seek(&self, handle: &types::InstanceHandle, time: f64) -> Result<(), JsValue> {
+    let time = finite(time, "time")?;
+
+    let _ = self.event_loop.send_event(UserEvents::Seek((time, handle.window_id)));
+    Ok(())
+  }
 
-    let _ = self.event_loop.send_event(UserEvents::UpdateShader((shader_source, callback, handle.window_id)));
+  // multiplies the wall clock, negative values play backwards
+  #[wasm_bindgen]
+  pub fn set_speed(&self, handle: &types::InstanceHandle, speed: f64) -> Result<(), JsValue> {
+    let speed = finite(speed, "speed")?;
+
+    let _ = self.event_loop.send_event(UserEvents::SetSpeed((speed, handle.window_id)));
+    Ok(())
+  }
+
+  // pauses and renders one frame advanced by `deltaTime` seconds, negative values step back.
+  // defaults to the fixed timestep, or 1/60 without one
+  #[wasm_bindgen]
+  pub fn step_frame(&self, handle: &types::InstanceHandle, delta_time: Option<f64>) -> Result<(), JsValue> {
+    let delta_time = delta_time.map(|delta_time| finite(delta_time, "deltaTime")).transpose()?;
+
+    let _ = self.event_loop.send_event(UserEvents::StepFrame((delta_time, handle.window_id)));
+    Ok(())
+  }
+
+  // advances time by exactly 1 / `fps` per frame regardless of the wall clock, with a
+  // reproducible seed and date. call `restart` to replay from the first frame, without
+  // `fps` the wall clock is used again
+  #[wasm_bindgen]
+  pub fn set_fixed_fps(&self, handle: &types::InstanceHandle, fps: Option<f64>) -> Result<(), JsValue> {
+    let fps = fps.map(|fps| finite(fps, "fps")).transpose()?;
+    if fps.is_some_and(|fps| fps <= 0.0) {
+      return Err(Error::InvalidParameter(String::from("`fps` must be positive")).into());
+    }
+
+    let _ = self.event_loop.send_event(UserEvents::SetFixedFps((fps, handle.window_id)));
+    Ok(())
+  }
+
+  // back to time and frame 0 with empty buffer passes
+  #[wasm_bindgen]
+  pub fn restart(&self, handle: &types::InstanceHandle) {
+    let _ = self.event_loop.send_event(UserEvents::Restart(handle.window_id));
+  }
+
+  // wraps time inside [start, end), call without arguments to stop looping
+  #[wasm_bindgen]
+  pub fn set_loop(&self, handle: &types::InstanceHandle, start: Option<f64>, end: Option<f64>) -> Result<(), JsValue> {
+    let loop_range = match (start, end) {
+      (None, None) => None,
+      (Some(start), Some(end)) if end > start => Some((finite(start, "start")?, finite(end, "end")?)),
+      (Some(_), Some(_)) => return Err(Error::InvalidParameter(String::from("loop `end` must be after `start`")).into()),
+      _ => return Err(Error::InvalidParameter(String::from("loop needs both `start` and `end`")).into()),
+    };
+
+    let _ = self.event_loop.send_event(UserEvents::SetLoop((loop_range, handle.window_id)));
+    Ok(())
   }
 
+  // resolves with the current time, frame and transport settings
   #[wasm_bindgen]
-  pub fn compile_shader(&self, handle: &types::InstanceHandle, ts_params: types::IUpdateShaderParams) {
+  pub fn playback_state(&self, handle: &types::InstanceHandle) -> js_sys::Promise {
+    let window_id = handle.window_id;
+    js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::GetPlaybackState((resolve, reject, window_id)));
+    })
+  }
+
+  // resolves with the backend, limits and features of the device the instance renders with
+  #[wasm_bindgen]
+  pub fn capabilities(&self, handle: &types::InstanceHandle) -> js_sys::Promise {
+    let window_id = handle.window_id;
+    js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::GetCapabilities((resolve, reject, window_id)));
+    })
+  }
+
+  // resolves with the number of exported frames, rejects with `EXPORT_CANCELLED` after
+  // `cancel_export`
+  #[wasm_bindgen]
+  pub fn export_frames(&self, handle: &types::InstanceHandle, ts_params: types::IExportParams) -> Result<js_sys::Promise, JsValue> {
+    let params = ts_params.into();
+    let (desc, format) = types::export_desc_from_js(&params)?;
+    desc.validate(u32::MAX)?;
+    let on_frame = types::require(types::get_function(&params, "onFrame")?, "onFrame")?;
+    let on_progress = types::get_function(&params, "onProgress")?;
+
+    let window_id = handle.window_id;
+    Ok(js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::Export((desc, format, on_frame.clone(), on_progress.clone(), resolve, reject, window_id)));
+    }))
+  }
+
+  // resolves with the gif file as a `Uint8Array`, rejects with `EXPORT_CANCELLED` after
+  // `cancel_export`
+  #[wasm_bindgen]
+  pub fn export_gif(&self, handle: &types::InstanceHandle, ts_params: types::IGifExportParams) -> Result<js_sys::Promise, JsValue> {
     let params = ts_params.into();
-    let shader_source = js_sys::Reflect::get(&params, &JsValue::from_str("shaderSource")).unwrap().as_string().unwrap();
-    let callback = js_sys::Reflect::get(&params, &JsValue::from_str("callback")).unwrap().dyn_into::<js_sys::Function>().unwrap();
+    let desc = types::gif_desc_from_js(&params)?;
+    desc.validate_gif()?;
+    let on_progress = types::get_function(&params, "onProgress")?;
+
+    let window_id = handle.window_id;
+    Ok(js_sys::Promise::new(&mut |resolve, reject| {
+      let _ = self.event_loop.send_event(UserEvents::ExportGif((desc, on_progress.clone(), resolve, reject, window_id)));
+    }))
+  }
 
-    let _ = self.event_loop.send_event(UserEvents::CompileShader((shader_source, callback, handle.window_id)));
+  #[wasm_bindgen]
+  pub fn cancel_export(&self, handle: &types::InstanceHandle) {
+    let _ = self.event_loop.send_event(UserEvents::CancelExport(handle.window_id));
   }
 
   #[wasm_bindgen]
@@ -289,6 +1127,20 @@ impl EventHandler {
   }
 }
 
+fn finite(value: f64, name: &str) -> Result<f32, Error> {
+  if !value.is_finite() {
+    return Err(Error::InvalidParameter(format!("`{}` must be a finite number, got {}", name, value)));
+  }
+  Ok(value as f32)
+}
+
+fn channel_index(channel: f64) -> Result<usize, Error> {
+  if channel.fract() != 0.0 || channel < 0.0 || channel >= MAX_CHANNELS as f64 {
+    return Err(Error::InvalidParameter(format!("channel index out of range: {}", channel)));
+  }
+  Ok(channel as usize)
+}
+
 /*
 #[wasm_bindgen]
 impl App {
@@ -0,0 +1,503 @@
+use std::{io::Write, path::PathBuf, sync::atomic::Ordering};
+
+use clap::{Parser, Subcommand};
+
+use crate::{app::EventHandler, error::Error, gfx::{buffer_pass::PassTarget, capture::CaptureError, export::{self, ExportDesc, ExportFormat}, gfx_state::GfxState, parameters, pipeline::{self, BlendMode, DrawDesc}}, shader_files::{ShaderFile, ShaderFiles}};
+
+#[derive(Debug, Parser)]
+#[command(name = "shaderx", about = "Preview, validate and render shaders")]
+pub struct Cli {
+  #[command(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+  /// Open a window previewing a shader
+  Run {
+    shader: PathBuf,
+    #[command(flatten)]
+    inputs: Inputs,
+  },
+  /// Compile-check shaders without opening a window
+  Validate {
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Print the annotated parameters of each shader as JSON
+    #[arg(long)]
+    schema: bool,
+  },
+  /// Render a single frame to an image
+  Render {
+    shader: PathBuf,
+    /// Output size as WIDTHxHEIGHT
+    #[arg(long, default_value = "800x450", value_parser = parse_size)]
+    size: (u32, u32),
+    /// Time in seconds of the rendered frame
+    #[arg(long, default_value_t = 0.0)]
+    time: f32,
+    #[arg(long, default_value = "frame.png")]
+    out: PathBuf,
+    #[command(flatten)]
+    inputs: Inputs,
+  },
+  /// Render a time range to a PNG sequence or raw RGBA frames on stdout,
+  /// --fps sets the frame rate (30 by default)
+  Export {
+    shader: PathBuf,
+    /// First frame time in seconds
+    #[arg(long, default_value_t = 0.0)]
+    start: f32,
+    /// Last frame time in seconds, included when it falls on a frame
+    #[arg(long)]
+    end: f32,
+    /// Output size as WIDTHxHEIGHT
+    #[arg(long, default_value = "800x450", value_parser = parse_size)]
+    size: (u32, u32),
+    /// png writes OUT/frame_00000.png and so on, rgba streams frames to stdout,
+    /// e.g. for `ffmpeg -f rawvideo -pix_fmt rgba -s WIDTHxHEIGHT -r FPS -i -`
+    #[arg(long, default_value = "png", value_parser = parse_export_format)]
+    format: ExportFormat,
+    /// Directory of the png sequence
+    #[arg(long, default_value = "frames")]
+    out: PathBuf,
+    #[command(flatten)]
+    inputs: Inputs,
+  },
+  /// Render a looping animated GIF of [START, END), --fps sets the frame rate
+  /// (15 by default, at most 50)
+  Gif {
+    shader: PathBuf,
+    /// Loop start in seconds
+    #[arg(long, default_value_t = 0.0)]
+    start: f32,
+    /// Loop end in seconds, the frame at END is left out
+    #[arg(long)]
+    end: f32,
+    /// Output size as WIDTHxHEIGHT
+    #[arg(long, default_value = "320x180", value_parser = parse_size)]
+    size: (u32, u32),
+    #[arg(long, default_value = "animation.gif")]
+    out: PathBuf,
+    #[command(flatten)]
+    inputs: Inputs,
+  },
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Inputs {
+  /// Buffer pass shader as PASS=PATH, PASS is one of a, b, c, d
+  #[arg(long = "buffer", value_parser = parse_buffer)]
+  buffers: Vec<(PassTarget, PathBuf)>,
+  /// Channel image as INDEX=PATH, INDEX is 0 to 3
+  #[arg(long = "channel", value_parser = parse_channel)]
+  channels: Vec<(usize, PathBuf)>,
+  /// User uniform as NAME=VALUE[,VALUE...], matrices are column major
+  #[arg(long = "uniform", value_parser = parse_uniform)]
+  uniforms: Vec<(String, Vec<f64>)>,
+  /// WGSL override constant as NAME=VALUE, VALUE is a number, true or false
+  #[arg(long = "override", value_parser = parse_override)]
+  overrides: Vec<(String, f64)>,
+  /// Storage buffer size as INDEX=BYTES, INDEX is 0 to 3. buffers are at least as large
+  /// as the shaders declare them
+  #[arg(long = "storage-buffer", value_parser = parse_storage_buffer)]
+  storage_buffers: Vec<(usize, u64)>,
+  /// Storage texture size as INDEX=WIDTHxHEIGHT, INDEX is 0 to 3. textures follow the
+  /// output size by default
+  #[arg(long = "storage-texture", value_parser = parse_storage_texture)]
+  storage_textures: Vec<(usize, (u32, u32))>,
+  /// Mesh drawn by the image pass with a depth buffer, an .obj or binary .glb file.
+  /// fragment-only shaders get `MeshInput`, the window orbits the camera on drag
+  #[arg(long)]
+  mesh: Option<PathBuf>,
+  /// Vertices drawn per instance by the image pass, a shader with its own vertex stage
+  /// builds geometry from the vertex index
+  #[arg(long, default_value_t = 3)]
+  vertex_count: u32,
+  #[arg(long, default_value_t = 1)]
+  instance_count: u32,
+  /// Primitive topology of the image pass: points, lines, line-strip, triangles or
+  /// triangle-strip
+  #[arg(long, default_value = "triangles", value_parser = parse_topology)]
+  topology: wgpu::PrimitiveTopology,
+  /// Face culling of the image pass: none, front or back
+  #[arg(long, default_value = "back", value_parser = parse_cull_mode)]
+  cull: CullMode,
+  /// Blending of the image pass: replace, alpha, premultiplied or additive
+  #[arg(long, default_value = "replace", value_parser = parse_blend)]
+  blend: BlendMode,
+  /// Background of the image pass as linear R,G,B,A
+  #[arg(long, default_value = "1,0,0,1", value_parser = parse_color)]
+  clear_color: wgpu::Color,
+  /// Advance time by exactly 1/FPS per frame with a reproducible seed and date,
+  /// renders always use a fixed timestep (60 by default)
+  #[arg(long, value_parser = parse_fps)]
+  fps: Option<f32>,
+}
+
+impl Inputs {
+  fn into_files(self, shader: PathBuf) -> ShaderFiles {
+    let mut passes = self.buffers;
+    passes.push((PassTarget::Image, shader));
+
+    ShaderFiles {
+      passes,
+      channels: self.channels,
+      uniforms: self.uniforms,
+      overrides: self.overrides,
+      fps: self.fps,
+      storage_buffers: self.storage_buffers,
+      storage_textures: self.storage_textures,
+      mesh: self.mesh,
+      draw: DrawDesc {
+        vertex_count: self.vertex_count,
+        instance_count: self.instance_count,
+        topology: self.topology,
+        cull_mode: self.cull,
+        blend: self.blend,
+        clear_color: self.clear_color,
+      },
+    }
+  }
+}
+
+// runs the command and returns the process exit code
+pub fn run(cli: Cli) -> i32 {
+  match cli.command {
+    None => {
+      let _event_handler = EventHandler::new();
+      0
+    },
+    Some(Command::Run { shader, inputs }) => {
+      let _event_handler = EventHandler::run(inputs.into_files(shader));
+      0
+    },
+    Some(Command::Validate { files, schema }) => pollster::block_on(validate(files, schema)),
+    Some(Command::Render { shader, size, time, out, inputs }) => {
+      let mut files = inputs.into_files(shader);
+      files.fps = files.fps.or(Some(60.0));
+      pollster::block_on(render(files, size, time, out))
+    },
+    Some(Command::Export { shader, start, end, size, format, out, inputs }) => {
+      let mut files = inputs.into_files(shader);
+      let desc = ExportDesc {
+        start,
+        end,
+        fps: files.fps.unwrap_or(30.0),
+        size: Some(size),
+      };
+      files.fps = None;
+      pollster::block_on(export(files, desc, format, out))
+    },
+    Some(Command::Gif { shader, start, end, size, out, inputs }) => {
+      let mut files = inputs.into_files(shader);
+      let desc = ExportDesc {
+        start,
+        end,
+        fps: files.fps.unwrap_or(15.0),
+        size: Some(size),
+      }.looped();
+      files.fps = None;
+      pollster::block_on(gif(files, desc, out))
+    },
+  }
+}
+
+async fn validate(files: Vec<PathBuf>, schema: bool) -> i32 {
+  let gfx = match GfxState::new_headless(1, 1).await {
+    Ok(gfx) => gfx,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      return 1;
+    },
+  };
+  let mut failed = 0;
+
+  for path in files.iter() {
+    let shader = match ShaderFile::read(path) {
+      Ok(shader) => shader,
+      Err(err) => {
+        eprintln!("{}: error: {}", path.display(), err);
+        failed += 1;
+        continue;
+      },
+    };
+
+    let (info, parameters) = gfx.compile_shader(PassTarget::Image, &shader.source, shader.language, &Default::default()).await;
+    shader.print_compilation_info(&info);
+    if info.messages.iter().any(|message| message.message_type == wgpu::CompilationMessageType::Error) {
+      failed += 1;
+    } else if schema {
+      println!("{}: {}", path.display(), parameters::schema_json(&parameters));
+    }
+  }
+
+  println!("{} of {} shaders compiled", files.len() - failed, files.len());
+  if failed > 0 { 1 } else { 0 }
+}
+
+async fn render(files: ShaderFiles, size: (u32, u32), time: f32, out: PathBuf) -> i32 {
+  let mut gfx = match GfxState::new_headless(size.0, size.1).await {
+    Ok(gfx) => gfx,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      return 1;
+    },
+  };
+  if !files.load(&mut gfx).await {
+    return 1;
+  }
+
+  let delta_time = 1.0 / files.fps.unwrap_or(60.0);
+  gfx.set_time(time - delta_time);
+  if let Err(err) = gfx.render_frame(delta_time) {
+    eprintln!("error: failed to render: {}", err);
+    return 1;
+  }
+
+  match gfx.save_frame(&out).await {
+    Ok(()) => {
+      println!("wrote {}", out.display());
+      0
+    },
+    Err(err) => {
+      eprintln!("{}: error: {}", out.display(), err);
+      1
+    },
+  }
+}
+
+async fn export(files: ShaderFiles, desc: ExportDesc, format: ExportFormat, out: PathBuf) -> i32 {
+  let mut gfx = match GfxState::new_headless(1, 1).await {
+    Ok(gfx) => gfx,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      return 1;
+    },
+  };
+  if !files.load(&mut gfx).await {
+    return 1;
+  }
+
+  if format == ExportFormat::Png {
+    if let Err(err) = std::fs::create_dir_all(&out) {
+      eprintln!("{}: error: {}", out.display(), err);
+      return 1;
+    }
+  }
+
+  // ctrl-c stops after the current frame, what was written so far stays
+  if !begin_export(&mut gfx, desc) {
+    return 1;
+  }
+
+  let gfx = std::cell::RefCell::new(gfx);
+  let mut stdout = std::io::stdout().lock();
+  let result = export::run(
+    &desc,
+    format,
+    |index| gfx.borrow_mut().render_export_frame(index),
+    |frame| {
+      match format {
+        ExportFormat::Png => std::fs::write(out.join(format!("frame_{:05}.png", frame.index)), &frame.data),
+        ExportFormat::Rgba => stdout.write_all(&frame.data),
+      }.map_err(CaptureError::Io)?;
+
+      eprint!("\rexported {}/{} frames", frame.index + 1, frame.frame_count);
+      Ok(())
+    },
+  ).await;
+  eprintln!();
+  gfx.borrow_mut().end_export();
+
+  match result {
+    Ok(frame_count) => {
+      if format == ExportFormat::Png {
+        println!("wrote {} frames to {}", frame_count, out.display());
+      }
+      0
+    },
+    Err(Error::ExportCancelled) => {
+      eprintln!("export cancelled");
+      130
+    },
+    Err(err) => {
+      eprintln!("error: {}", err);
+      1
+    },
+  }
+}
+
+async fn gif(files: ShaderFiles, desc: ExportDesc, out: PathBuf) -> i32 {
+  if let Err(err) = desc.validate_gif() {
+    eprintln!("error: {}", err);
+    return 1;
+  }
+
+  let mut gfx = match GfxState::new_headless(1, 1).await {
+    Ok(gfx) => gfx,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      return 1;
+    },
+  };
+  if !files.load(&mut gfx).await {
+    return 1;
+  }
+
+  // nothing is written when ctrl-c stops the export
+  if !begin_export(&mut gfx, desc) {
+    return 1;
+  }
+
+  let gfx = std::cell::RefCell::new(gfx);
+  let result = export::encode_gif(
+    &desc,
+    |index| gfx.borrow_mut().render_export_frame(index),
+    |done, total| eprint!("\rencoded {}/{} frames", done, total),
+  ).await;
+  eprintln!();
+  gfx.borrow_mut().end_export();
+
+  let bytes = match result {
+    Ok(bytes) => bytes,
+    Err(Error::ExportCancelled) => {
+      eprintln!("export cancelled");
+      return 130;
+    },
+    Err(err) => {
+      eprintln!("error: {}", err);
+      return 1;
+    },
+  };
+
+  match std::fs::write(&out, bytes) {
+    Ok(()) => {
+      println!("wrote {}", out.display());
+      0
+    },
+    Err(err) => {
+      eprintln!("{}: error: {}", out.display(), err);
+      1
+    },
+  }
+}
+
+// starts the export and lets ctrl-c cancel it
+fn begin_export(gfx: &mut GfxState, desc: ExportDesc) -> bool {
+  let cancel = match gfx.begin_export(desc) {
+    Ok(cancel) => cancel,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      return false;
+    },
+  };
+
+  if let Err(err) = ctrlc::set_handler(move || cancel.store(true, Ordering::Relaxed)) {
+    log::warn!("[cli] failed to install ctrl-c handler: {}", err);
+  }
+  true
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+  let (width, height) = value.split_once('x').ok_or("expected WIDTHxHEIGHT")?;
+  let width = width.parse::<u32>().map_err(|err| err.to_string())?;
+  let height = height.parse::<u32>().map_err(|err| err.to_string())?;
+  Ok((width, height))
+}
+
+fn parse_buffer(value: &str) -> Result<(PassTarget, PathBuf), String> {
+  let (name, path) = value.split_once('=').ok_or("expected PASS=PATH")?;
+  let pass = match name.to_ascii_lowercase().as_str() {
+    "a" => PassTarget::Buffer(0),
+    "b" => PassTarget::Buffer(1),
+    "c" => PassTarget::Buffer(2),
+    "d" => PassTarget::Buffer(3),
+    _ => return Err(format!("unknown buffer pass: {}", name)),
+  };
+  Ok((pass, PathBuf::from(path)))
+}
+
+fn parse_channel(value: &str) -> Result<(usize, PathBuf), String> {
+  let (index, path) = value.split_once('=').ok_or("expected INDEX=PATH")?;
+  let index = index.parse::<usize>().map_err(|err| err.to_string())?;
+  if index >= crate::gfx::channels::MAX_CHANNELS {
+    return Err(format!("channel index out of range: {}", index));
+  }
+  Ok((index, PathBuf::from(path)))
+}
+
+fn parse_storage_buffer(value: &str) -> Result<(usize, u64), String> {
+  let (index, size) = value.split_once('=').ok_or("expected INDEX=BYTES")?;
+  let index = parse_storage_index(index)?;
+  let size = size.parse::<u64>().map_err(|err| err.to_string())?;
+  Ok((index, size))
+}
+
+fn parse_storage_texture(value: &str) -> Result<(usize, (u32, u32)), String> {
+  let (index, size) = value.split_once('=').ok_or("expected INDEX=WIDTHxHEIGHT")?;
+  Ok((parse_storage_index(index)?, parse_size(size)?))
+}
+
+fn parse_storage_index(index: &str) -> Result<usize, String> {
+  let index = index.parse::<usize>().map_err(|err| err.to_string())?;
+  if index >= crate::gfx::storage::MAX_STORAGE_BUFFERS.max(crate::gfx::storage::MAX_STORAGE_TEXTURES) {
+    return Err(format!("storage index out of range: {}", index));
+  }
+  Ok(index)
+}
+
+fn parse_topology(value: &str) -> Result<wgpu::PrimitiveTopology, String> {
+  pipeline::topology_from_name(value).ok_or_else(|| format!("unknown topology: {}", value))
+}
+
+// clap makes fields typed `Option` optional, `none` is a value of its own here
+type CullMode = Option<wgpu::Face>;
+
+fn parse_cull_mode(value: &str) -> Result<CullMode, String> {
+  pipeline::cull_mode_from_name(value).ok_or_else(|| format!("unknown cull mode: {}", value))
+}
+
+fn parse_blend(value: &str) -> Result<BlendMode, String> {
+  BlendMode::from_name(value).ok_or_else(|| format!("unknown blend mode: {}", value))
+}
+
+fn parse_color(value: &str) -> Result<wgpu::Color, String> {
+  let values = value.split(',')
+    .map(|value| value.trim().parse::<f64>().map_err(|err| format!("{}: {}", value, err)))
+    .collect::<Result<Vec<_>, _>>()?;
+  let [r, g, b, a] = values[..] else {
+    return Err(String::from("expected R,G,B,A"));
+  };
+  Ok(wgpu::Color { r, g, b, a })
+}
+
+fn parse_export_format(value: &str) -> Result<ExportFormat, String> {
+  ExportFormat::from_name(value).ok_or_else(|| format!("unknown export format: {}, expected png or rgba", value))
+}
+
+fn parse_fps(value: &str) -> Result<f32, String> {
+  let fps = value.parse::<f32>().map_err(|err| err.to_string())?;
+  if !fps.is_finite() || fps <= 0.0 {
+    return Err(format!("fps must be positive: {}", value));
+  }
+  Ok(fps)
+}
+
+fn parse_override(value: &str) -> Result<(String, f64), String> {
+  let (name, value) = value.split_once('=').ok_or("expected NAME=VALUE")?;
+  let value = match value.trim() {
+    "true" => 1.0,
+    "false" => 0.0,
+    value => value.parse::<f64>().map_err(|err| format!("{}: {}", value, err))?,
+  };
+  Ok((name.to_string(), value))
+}
+
+fn parse_uniform(value: &str) -> Result<(String, Vec<f64>), String> {
+  let (name, values) = value.split_once('=').ok_or("expected NAME=VALUE[,VALUE...]")?;
+  let values = values.split(',')
+    .map(|value| value.trim().parse::<f64>().map_err(|err| format!("{}: {}", value, err)))
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok((name.to_string(), values))
+}
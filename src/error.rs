@@ -0,0 +1,119 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+use crate::gfx::capture::CaptureError;
+
+#[derive(Debug)]
+pub enum Error {
+  WindowCreation(winit::error::OsError),
+  SurfaceCreation(wgpu::CreateSurfaceError),
+  // the adapter can't present to the surface
+  SurfaceUnsupported,
+  AdapterUnavailable,
+  DeviceRequest(wgpu::RequestDeviceError),
+  // the shader was rejected while building its pipeline, the previous one is kept
+  Pipeline(Vec<wgpu::CompilationMessage>),
+  InvalidParameter(String),
+  #[cfg(target_arch = "wasm32")]
+  ContainerNotFound(String),
+  InstanceNotFound,
+  Image(image::ImageError),
+  Capture(CaptureError),
+  ExportInProgress,
+  ExportCancelled,
+  Mesh(String),
+  // the device lacks a feature, e.g. compute shaders on webgl2
+  Unsupported(String),
+}
+
+impl Error {
+  // stable identifiers exposed to js as `error.code`, don't rename
+  pub fn code(&self) -> &'static str {
+    match self {
+      Error::WindowCreation(_) => "WINDOW_CREATION_FAILED",
+      Error::SurfaceCreation(_) => "SURFACE_CREATION_FAILED",
+      Error::SurfaceUnsupported => "SURFACE_UNSUPPORTED",
+      Error::AdapterUnavailable => "ADAPTER_UNAVAILABLE",
+      Error::DeviceRequest(_) => "DEVICE_REQUEST_FAILED",
+      Error::Pipeline(_) => "PIPELINE_CREATION_FAILED",
+      Error::InvalidParameter(_) => "INVALID_PARAMETER",
+      #[cfg(target_arch = "wasm32")]
+      Error::ContainerNotFound(_) => "CONTAINER_NOT_FOUND",
+      Error::InstanceNotFound => "INSTANCE_NOT_FOUND",
+      Error::Image(_) => "IMAGE_DECODE_FAILED",
+      Error::Capture(_) => "CAPTURE_FAILED",
+      Error::ExportInProgress => "EXPORT_IN_PROGRESS",
+      Error::ExportCancelled => "EXPORT_CANCELLED",
+      Error::Mesh(_) => "MESH_LOAD_FAILED",
+      Error::Unsupported(_) => "UNSUPPORTED",
+    }
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::WindowCreation(err) => write!(f, "failed to create window: {}", err),
+      Error::SurfaceCreation(err) => write!(f, "failed to create surface: {}", err),
+      Error::SurfaceUnsupported => write!(f, "surface is not supported by the adapter"),
+      Error::AdapterUnavailable => write!(f, "no compatible adapter found"),
+      Error::DeviceRequest(err) => write!(f, "failed to create device: {}", err),
+      Error::Pipeline(messages) => match messages.first() {
+        Some(message) => write!(f, "failed to create pipeline: {}", message.message),
+        None => write!(f, "failed to create pipeline"),
+      },
+      Error::InvalidParameter(message) => write!(f, "invalid parameter: {}", message),
+      #[cfg(target_arch = "wasm32")]
+      Error::ContainerNotFound(id) => write!(f, "no element with id `{}`", id),
+      Error::InstanceNotFound => write!(f, "instance was destroyed or never created"),
+      Error::Image(err) => write!(f, "failed to load image: {}", err),
+      Error::Capture(err) => write!(f, "{}", err),
+      Error::ExportInProgress => write!(f, "another export is still running"),
+      Error::ExportCancelled => write!(f, "export was cancelled"),
+      Error::Mesh(message) => write!(f, "failed to load mesh: {}", message),
+      Error::Unsupported(feature) => write!(f, "{} not supported by this device", feature),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+  fn from(err: wgpu::CreateSurfaceError) -> Self {
+    Error::SurfaceCreation(err)
+  }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+  fn from(err: wgpu::RequestDeviceError) -> Self {
+    Error::DeviceRequest(err)
+  }
+}
+
+impl From<winit::error::OsError> for Error {
+  fn from(err: winit::error::OsError) -> Self {
+    Error::WindowCreation(err)
+  }
+}
+
+impl From<image::ImageError> for Error {
+  fn from(err: image::ImageError) -> Self {
+    Error::Image(err)
+  }
+}
+
+impl From<CaptureError> for Error {
+  fn from(err: CaptureError) -> Self {
+    Error::Capture(err)
+  }
+}
+
+// a js `Error` with the stable `code` attached
+impl From<Error> for JsValue {
+  fn from(err: Error) -> Self {
+    let js_error = js_sys::Error::new(&err.to_string());
+    let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("code"), &JsValue::from_str(err.code()));
+    js_error.into()
+  }
+}
@@ -0,0 +1,297 @@
+use super::{pipeline::Pipeline, storage::{Storage, MAX_STORAGE_BUFFERS, MAX_STORAGE_TEXTURES, STORAGE_BUFFER_BINDING, STORAGE_TEXTURE_BINDING, STORAGE_TEXTURE_FORMAT}};
+
+pub const MAX_BUFFER_PASSES: usize = 4;
+
+// wgsl declaration of the buffer pass bindings, every pass can sample every buffer
+pub const BUFFERS_WGSL: &str = include_str!("buffers.wgsl");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PassTarget {
+  Image,
+  Buffer(usize),
+}
+
+impl PassTarget {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "image" => Some(PassTarget::Image),
+      "bufferA" => Some(PassTarget::Buffer(0)),
+      "bufferB" => Some(PassTarget::Buffer(1)),
+      "bufferC" => Some(PassTarget::Buffer(2)),
+      "bufferD" => Some(PassTarget::Buffer(3)),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct BufferPass {
+  pub pipeline: Pipeline,
+  textures: [wgpu::Texture; 2],
+  views: [wgpu::TextureView; 2],
+  // index of the texture holding the most recent output
+  current: usize,
+}
+
+#[derive(Debug)]
+pub struct BufferPassCreateDesc<'a> {
+  pub device: &'a wgpu::Device,
+  pub pipeline: Pipeline,
+  pub format: wgpu::TextureFormat,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl BufferPass {
+  pub fn new(create_desc: BufferPassCreateDesc) -> Self {
+    let (textures, views) = Self::create_textures(create_desc.device, create_desc.format, create_desc.width, create_desc.height);
+
+    Self {
+      pipeline: create_desc.pipeline,
+      textures,
+      views,
+      current: 0,
+    }
+  }
+
+  pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+    let format = self.textures[0].format();
+    let (textures, views) = Self::create_textures(device, format, width, height);
+    self.textures = textures;
+    self.views = views;
+    self.current = 0;
+  }
+
+  // view sampled by the shaders, previous frame until this pass has rendered
+  pub fn read_view(&self) -> &wgpu::TextureView {
+    &self.views[self.current]
+  }
+
+  pub fn write_view(&self) -> &wgpu::TextureView {
+    &self.views[1 - self.current]
+  }
+
+  pub fn swap(&mut self) {
+    self.current = 1 - self.current;
+  }
+
+  fn create_textures(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> ([wgpu::Texture; 2], [wgpu::TextureView; 2]) {
+    let create_texture = || device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("buffer pass texture"),
+      size: wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+      view_formats: &[],
+    });
+
+    let textures = [create_texture(), create_texture()];
+    let views = [
+      textures[0].create_view(&wgpu::TextureViewDescriptor::default()),
+      textures[1].create_view(&wgpu::TextureViewDescriptor::default()),
+    ];
+
+    (textures, views)
+  }
+}
+
+#[derive(Debug)]
+pub struct BufferBindings {
+  pub bind_group_layout: wgpu::BindGroupLayout,
+  // storage is writable from compute entry points, `None` without storage support
+  pub compute_bind_group_layout: Option<wgpu::BindGroupLayout>,
+  pub group: u32,
+  sampler: wgpu::Sampler,
+  empty_view: wgpu::TextureView,
+}
+
+#[derive(Debug)]
+pub struct BufferBindingsCreateDesc<'a> {
+  pub device: &'a wgpu::Device,
+  pub group: u32,
+  // adds the storage bindings and the compute layout
+  pub storage: bool,
+  // storage buffers are readable from vertex stages too
+  pub vertex_storage: bool,
+}
+
+impl BufferBindings {
+  pub fn new(create_desc: &BufferBindingsCreateDesc) -> Self {
+    let device = create_desc.device;
+
+    let mut entries = texture_layout_entries(wgpu::ShaderStages::VERTEX_FRAGMENT);
+    if create_desc.storage {
+      let buffer_visibility = match create_desc.vertex_storage {
+        true => wgpu::ShaderStages::VERTEX_FRAGMENT,
+        false => wgpu::ShaderStages::FRAGMENT,
+      };
+      entries.extend((0..MAX_STORAGE_BUFFERS as u32).map(|i| storage_buffer_layout_entry(i, buffer_visibility, true)));
+      entries.extend((0..MAX_STORAGE_TEXTURES as u32).map(|i| wgpu::BindGroupLayoutEntry {
+        binding: STORAGE_TEXTURE_BINDING + i,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+          sample_type: wgpu::TextureSampleType::Float { filterable: true },
+          view_dimension: wgpu::TextureViewDimension::D2,
+          multisampled: false,
+        },
+        count: None,
+      }));
+    }
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &entries,
+      label: Some("buffer bind group layout"),
+    });
+
+    let compute_bind_group_layout = create_desc.storage.then(|| {
+      let mut entries = texture_layout_entries(wgpu::ShaderStages::COMPUTE);
+      entries.extend((0..MAX_STORAGE_BUFFERS as u32).map(|i| storage_buffer_layout_entry(i, wgpu::ShaderStages::COMPUTE, false)));
+      entries.extend((0..MAX_STORAGE_TEXTURES as u32).map(|i| wgpu::BindGroupLayoutEntry {
+        binding: STORAGE_TEXTURE_BINDING + i,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+          access: wgpu::StorageTextureAccess::WriteOnly,
+          format: STORAGE_TEXTURE_FORMAT,
+          view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+      }));
+
+      device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &entries,
+        label: Some("compute buffer bind group layout"),
+      })
+    });
+
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("buffer sampler"),
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      ..Default::default()
+    });
+
+    // bound in place of buffers that have no shader
+    let empty_texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("empty buffer texture"),
+      size: wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: wgpu::TextureFormat::Rgba8Unorm,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING,
+      view_formats: &[],
+    });
+    let empty_view = empty_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    Self {
+      bind_group_layout,
+      compute_bind_group_layout,
+      group: create_desc.group,
+      sampler,
+      empty_view,
+    }
+  }
+
+  // `storage` is required when the layout has storage bindings
+  pub fn create_bind_group(&self, device: &wgpu::Device, buffers: &[Option<BufferPass>], storage: Option<&Storage>) -> wgpu::BindGroup {
+    let mut entries = self.texture_entries(buffers);
+    if let Some(storage) = storage {
+      entries.extend(storage_entries(storage));
+    }
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("buffer bind group"),
+      entries: &entries,
+      layout: &self.bind_group_layout,
+    })
+  }
+
+  // same resources as `create_bind_group`, with the storage textures bound for writing
+  pub fn create_compute_bind_group(&self, device: &wgpu::Device, buffers: &[Option<BufferPass>], storage: &Storage) -> Option<wgpu::BindGroup> {
+    let layout = self.compute_bind_group_layout.as_ref()?;
+    let mut entries = self.texture_entries(buffers);
+    entries.extend(storage_entries(storage));
+
+    Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("compute buffer bind group"),
+      entries: &entries,
+      layout,
+    }))
+  }
+
+  fn texture_entries<'a>(&'a self, buffers: &'a [Option<BufferPass>]) -> Vec<wgpu::BindGroupEntry<'a>> {
+    let mut entries: Vec<wgpu::BindGroupEntry> = buffers.iter().enumerate().map(|(i, buffer)| wgpu::BindGroupEntry {
+      binding: i as u32,
+      resource: wgpu::BindingResource::TextureView(buffer.as_ref().map(|b| b.read_view()).unwrap_or(&self.empty_view)),
+    }).collect();
+
+    entries.push(wgpu::BindGroupEntry {
+      binding: MAX_BUFFER_PASSES as u32,
+      resource: wgpu::BindingResource::Sampler(&self.sampler),
+    });
+
+    entries
+  }
+}
+
+// the buffer textures and their sampler
+fn texture_layout_entries(visibility: wgpu::ShaderStages) -> Vec<wgpu::BindGroupLayoutEntry> {
+  let mut entries: Vec<wgpu::BindGroupLayoutEntry> = (0..MAX_BUFFER_PASSES as u32).map(|i| wgpu::BindGroupLayoutEntry {
+    binding: i,
+    visibility,
+    ty: wgpu::BindingType::Texture {
+      sample_type: wgpu::TextureSampleType::Float { filterable: true },
+      view_dimension: wgpu::TextureViewDimension::D2,
+      multisampled: false,
+    },
+    count: None,
+  }).collect();
+
+  entries.push(wgpu::BindGroupLayoutEntry {
+    binding: MAX_BUFFER_PASSES as u32,
+    visibility,
+    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+    count: None,
+  });
+
+  entries
+}
+
+fn storage_buffer_layout_entry(index: u32, visibility: wgpu::ShaderStages, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+  wgpu::BindGroupLayoutEntry {
+    binding: STORAGE_BUFFER_BINDING + index,
+    visibility,
+    ty: wgpu::BindingType::Buffer {
+      ty: wgpu::BufferBindingType::Storage { read_only },
+      has_dynamic_offset: false,
+      min_binding_size: None,
+    },
+    count: None,
+  }
+}
+
+fn storage_entries(storage: &Storage) -> impl Iterator<Item = wgpu::BindGroupEntry<'_>> {
+  let buffers = (0..MAX_STORAGE_BUFFERS).map(|i| wgpu::BindGroupEntry {
+    binding: STORAGE_BUFFER_BINDING + i as u32,
+    resource: storage.buffer(i).as_entire_binding(),
+  });
+  let textures = (0..MAX_STORAGE_TEXTURES).map(|i| wgpu::BindGroupEntry {
+    binding: STORAGE_TEXTURE_BINDING + i as u32,
+    resource: wgpu::BindingResource::TextureView(storage.view(i)),
+  });
+  buffers.chain(textures)
+}
@@ -0,0 +1,11 @@
+// shaderx buffer passes
+//
+// bound at @group(1) for every pipeline. each buffer holds the latest output of
+// its pass: passes that already ran this frame expose the current frame, the
+// others (including the pass reading itself) expose the previous frame.
+// buffers without a shader read as transparent black.
+@group(1) @binding(0) var buffer_a: texture_2d<f32>;
+@group(1) @binding(1) var buffer_b: texture_2d<f32>;
+@group(1) @binding(2) var buffer_c: texture_2d<f32>;
+@group(1) @binding(3) var buffer_d: texture_2d<f32>;
+@group(1) @binding(4) var buffer_sampler: sampler;
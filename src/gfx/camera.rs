@@ -0,0 +1,62 @@
+use glam::{Mat4, Vec3};
+
+// radians per pixel dragged
+const ROTATE_SPEED: f32 = 0.01;
+// distance factor per scrolled line
+const ZOOM_SPEED: f32 = 1.1;
+const MIN_DISTANCE: f32 = 1.1;
+const MAX_DISTANCE: f32 = 100.0;
+// keeps the up vector away from the view direction
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+const NEAR: f32 = 0.01;
+const FAR: f32 = 1000.0;
+
+// orbits the origin, where meshes are centered and scaled to fit the unit sphere
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+  // radians around the y axis, 0 looks down -z
+  pub yaw: f32,
+  // radians above the horizon
+  pub pitch: f32,
+  pub distance: f32,
+  // vertical field of view in radians
+  pub fov_y: f32,
+}
+
+impl Default for OrbitCamera {
+  fn default() -> Self {
+    Self {
+      yaw: 0.0,
+      pitch: 0.0,
+      distance: 3.0,
+      fov_y: 45f32.to_radians(),
+    }
+  }
+}
+
+impl OrbitCamera {
+  // dragging right turns the mesh right, dragging up tilts it towards the viewer
+  pub fn rotate(&mut self, dx: f32, dy: f32) {
+    self.yaw -= dx * ROTATE_SPEED;
+    self.pitch = (self.pitch + dy * ROTATE_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+  }
+
+  // positive `lines` move closer
+  pub fn zoom(&mut self, lines: f32) {
+    self.distance = (self.distance * ZOOM_SPEED.powf(-lines)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+  }
+
+  pub fn position(&self) -> Vec3 {
+    let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+    Vec3::new(sin_yaw * cos_pitch, sin_pitch, cos_yaw * cos_pitch) * self.distance
+  }
+
+  pub fn view(&self) -> Mat4 {
+    Mat4::look_at_rh(self.position(), Vec3::ZERO, Vec3::Y)
+  }
+
+  pub fn projection(&self, aspect: f32) -> Mat4 {
+    Mat4::perspective_rh(self.fov_y, aspect.max(f32::EPSILON), NEAR, FAR)
+  }
+}
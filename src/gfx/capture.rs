@@ -0,0 +1,151 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum CaptureError {
+  Map(wgpu::BufferAsyncError),
+  UnsupportedFormat(wgpu::TextureFormat),
+  Encode(image::ImageError),
+  EncodeGif(gif::EncodingError),
+  Io(std::io::Error),
+}
+
+impl fmt::Display for CaptureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CaptureError::Map(err) => write!(f, "failed to map capture buffer: {}", err),
+      CaptureError::UnsupportedFormat(format) => write!(f, "unsupported capture format: {:?}", format),
+      CaptureError::Encode(err) => write!(f, "failed to encode png: {}", err),
+      CaptureError::EncodeGif(err) => write!(f, "failed to encode gif: {}", err),
+      CaptureError::Io(err) => write!(f, "failed to write capture: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for CaptureError {}
+
+#[derive(Debug)]
+pub struct FrameCapture {
+  buffer: wgpu::Buffer,
+  format: wgpu::TextureFormat,
+  width: u32,
+  height: u32,
+  padded_bytes_per_row: u32,
+  opaque: bool,
+  receiver: Option<futures_channel::oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+#[derive(Debug)]
+pub struct FrameCaptureCreateDesc<'a> {
+  pub device: &'a wgpu::Device,
+  pub encoder: &'a mut wgpu::CommandEncoder,
+  pub texture: &'a wgpu::Texture,
+  // force alpha to 1, matches what an opaque surface shows
+  pub opaque: bool,
+}
+
+impl FrameCapture {
+  // records the copy of `texture` into a staging buffer, submit the encoder before `map`
+  pub fn new(create_desc: FrameCaptureCreateDesc) -> Self {
+    let texture = create_desc.texture;
+    let width = texture.width();
+    let height = texture.height();
+
+    let unpadded_bytes_per_row = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+    let buffer = create_desc.device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("capture buffer"),
+      size: (padded_bytes_per_row * height) as u64,
+      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+      mapped_at_creation: false,
+    });
+
+    create_desc.encoder.copy_texture_to_buffer(
+      wgpu::ImageCopyTexture {
+        texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+        aspect: wgpu::TextureAspect::All,
+      },
+      wgpu::ImageCopyBuffer {
+        buffer: &buffer,
+        layout: wgpu::ImageDataLayout {
+          offset: 0,
+          bytes_per_row: Some(padded_bytes_per_row),
+          rows_per_image: Some(height),
+        },
+      },
+      texture.size(),
+    );
+
+    Self {
+      buffer,
+      format: texture.format(),
+      width,
+      height,
+      padded_bytes_per_row,
+      opaque: create_desc.opaque,
+      receiver: None,
+    }
+  }
+
+  pub fn map(&mut self, device: &wgpu::Device) {
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    self.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+      let _ = sender.send(result);
+    });
+    self.receiver = Some(receiver);
+
+    // the browser resolves the mapping on its own, everything else needs a poll
+    device.poll(wgpu::Maintain::Wait);
+  }
+
+  // tightly packed rgba8 rows, top row first
+  pub async fn into_rgba(self) -> Result<(u32, u32, Vec<u8>), CaptureError> {
+    if let Some(receiver) = self.receiver {
+      match receiver.await {
+        Ok(result) => result.map_err(CaptureError::Map)?,
+        Err(_) => return Err(CaptureError::Map(wgpu::BufferAsyncError)),
+      }
+    }
+
+    // srgb formats already hold the encoded values png expects, only the channel order differs
+    let swizzle = match self.format {
+      wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+      wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+      format => return Err(CaptureError::UnsupportedFormat(format)),
+    };
+
+    let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+    {
+      let data = self.buffer.slice(..).get_mapped_range();
+      for row in data.chunks(self.padded_bytes_per_row as usize) {
+        for pixel in row[..(self.width * 4) as usize].chunks_exact(4) {
+          let (r, b) = if swizzle { (pixel[2], pixel[0]) } else { (pixel[0], pixel[2]) };
+          let a = if self.opaque { 255 } else { pixel[3] };
+          pixels.extend_from_slice(&[r, pixel[1], b, a]);
+        }
+      }
+    }
+    self.buffer.unmap();
+
+    Ok((self.width, self.height, pixels))
+  }
+
+  pub async fn into_png(self) -> Result<Vec<u8>, CaptureError> {
+    let (width, height, pixels) = self.into_rgba().await?;
+    encode_png(width, height, &pixels)
+  }
+}
+
+pub fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>, CaptureError> {
+  use image::ImageEncoder;
+
+  let mut png = Vec::new();
+  image::codecs::png::PngEncoder::new(&mut png)
+    .write_image(pixels, width, height, image::ExtendedColorType::Rgba8)
+    .map_err(CaptureError::Encode)?;
+
+  Ok(png)
+}
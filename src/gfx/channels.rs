@@ -0,0 +1,210 @@
+pub const MAX_CHANNELS: usize = 4;
+
+// wgsl declaration of the texture channel bindings
+pub const CHANNELS_WGSL: &str = include_str!("channels.wgsl");
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelSamplerDesc {
+  pub filter: wgpu::FilterMode,
+  pub address_mode: wgpu::AddressMode,
+  pub flip_y: bool,
+}
+
+impl Default for ChannelSamplerDesc {
+  fn default() -> Self {
+    Self {
+      filter: wgpu::FilterMode::Linear,
+      address_mode: wgpu::AddressMode::Repeat,
+      flip_y: true,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct Channel {
+  pub view: wgpu::TextureView,
+  pub sampler: wgpu::Sampler,
+  pub width: u32,
+  pub height: u32,
+}
+
+#[derive(Debug)]
+pub struct ChannelCreateDesc<'a> {
+  pub device: &'a wgpu::Device,
+  pub queue: &'a wgpu::Queue,
+  pub data: &'a [u8],
+  pub sampler: ChannelSamplerDesc,
+  pub max_dimension: u32,
+}
+
+impl Channel {
+  pub fn new(create_desc: &ChannelCreateDesc) -> Result<Self, image::ImageError> {
+    let device = create_desc.device;
+    let mut image = image::load_from_memory(create_desc.data)?;
+
+    if image.width() > create_desc.max_dimension || image.height() > create_desc.max_dimension {
+      log::warn!("[gfx] channel image {}x{} exceeds the texture limit, downscaling", image.width(), image.height());
+      image = image.resize(create_desc.max_dimension, create_desc.max_dimension, image::imageops::FilterType::Triangle);
+    }
+
+    // shaders expect uv (0, 0) at the bottom left, like the rest of the frame
+    if create_desc.sampler.flip_y {
+      image = image.flipv();
+    }
+
+    let image = image.to_rgba8();
+    let (width, height) = image.dimensions();
+
+    let size = wgpu::Extent3d {
+      width,
+      height,
+      depth_or_array_layers: 1,
+    };
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("channel texture"),
+      size,
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: wgpu::TextureFormat::Rgba8Unorm,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+      view_formats: &[],
+    });
+
+    create_desc.queue.write_texture(
+      wgpu::ImageCopyTexture {
+        texture: &texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+        aspect: wgpu::TextureAspect::All,
+      },
+      &image,
+      wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: Some(4 * width),
+        rows_per_image: Some(height),
+      },
+      size,
+    );
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = create_sampler(device, &create_desc.sampler);
+
+    Ok(Self {
+      view,
+      sampler,
+      width,
+      height,
+    })
+  }
+}
+
+#[derive(Debug)]
+pub struct ChannelBindings {
+  pub bind_group_layout: wgpu::BindGroupLayout,
+  pub bind_group: wgpu::BindGroup,
+  pub group: u32,
+  empty_view: wgpu::TextureView,
+  empty_sampler: wgpu::Sampler,
+}
+
+impl ChannelBindings {
+  pub fn new(device: &wgpu::Device, group: u32) -> Self {
+    let textures = (0..MAX_CHANNELS as u32).map(|i| wgpu::BindGroupLayoutEntry {
+      binding: i,
+      visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
+      ty: wgpu::BindingType::Texture {
+        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        view_dimension: wgpu::TextureViewDimension::D2,
+        multisampled: false,
+      },
+      count: None,
+    });
+
+    let samplers = (0..MAX_CHANNELS as u32).map(|i| wgpu::BindGroupLayoutEntry {
+      binding: MAX_CHANNELS as u32 + i,
+      visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
+      ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+      count: None,
+    });
+
+    let entries: Vec<wgpu::BindGroupLayoutEntry> = textures.chain(samplers).collect();
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &entries,
+      label: Some("channel bind group layout"),
+    });
+
+    // bound in place of channels without an image
+    let empty_texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("empty channel texture"),
+      size: wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: wgpu::TextureFormat::Rgba8Unorm,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING,
+      view_formats: &[],
+    });
+    let empty_view = empty_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let empty_sampler = create_sampler(device, &ChannelSamplerDesc::default());
+
+    let bind_group = Self::create_bind_group(device, &bind_group_layout, &[None, None, None, None], &empty_view, &empty_sampler);
+
+    Self {
+      bind_group_layout,
+      bind_group,
+      group,
+      empty_view,
+      empty_sampler,
+    }
+  }
+
+  pub fn update(&mut self, device: &wgpu::Device, channels: &[Option<Channel>]) {
+    self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, channels, &self.empty_view, &self.empty_sampler);
+  }
+
+  fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    channels: &[Option<Channel>],
+    empty_view: &wgpu::TextureView,
+    empty_sampler: &wgpu::Sampler,
+  ) -> wgpu::BindGroup {
+    let textures = channels.iter().enumerate().map(|(i, channel)| wgpu::BindGroupEntry {
+      binding: i as u32,
+      resource: wgpu::BindingResource::TextureView(channel.as_ref().map(|c| &c.view).unwrap_or(empty_view)),
+    });
+
+    let samplers = channels.iter().enumerate().map(|(i, channel)| wgpu::BindGroupEntry {
+      binding: (MAX_CHANNELS + i) as u32,
+      resource: wgpu::BindingResource::Sampler(channel.as_ref().map(|c| &c.sampler).unwrap_or(empty_sampler)),
+    });
+
+    let entries: Vec<wgpu::BindGroupEntry> = textures.chain(samplers).collect();
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("channel bind group"),
+      entries: &entries,
+      layout,
+    })
+  }
+}
+
+fn create_sampler(device: &wgpu::Device, desc: &ChannelSamplerDesc) -> wgpu::Sampler {
+  device.create_sampler(&wgpu::SamplerDescriptor {
+    label: Some("channel sampler"),
+    address_mode_u: desc.address_mode,
+    address_mode_v: desc.address_mode,
+    address_mode_w: desc.address_mode,
+    mag_filter: desc.filter,
+    min_filter: desc.filter,
+    mipmap_filter: wgpu::FilterMode::Nearest,
+    ..Default::default()
+  })
+}
@@ -0,0 +1,13 @@
+// shaderx texture channels
+//
+// bound at @group(2) for every pipeline. each channel has its own sampler so
+// filtering and wrapping can be chosen per image. channels without an image
+// read as transparent black, see `common_uniforms.channel_resolution`.
+@group(2) @binding(0) var channel0: texture_2d<f32>;
+@group(2) @binding(1) var channel1: texture_2d<f32>;
+@group(2) @binding(2) var channel2: texture_2d<f32>;
+@group(2) @binding(3) var channel3: texture_2d<f32>;
+@group(2) @binding(4) var channel0_sampler: sampler;
+@group(2) @binding(5) var channel1_sampler: sampler;
+@group(2) @binding(6) var channel2_sampler: sampler;
+@group(2) @binding(7) var channel3_sampler: sampler;
@@ -0,0 +1,46 @@
+// shaderx common uniforms
+//
+// bound at @group(0) @binding(0) for every pipeline, the layout matches
+// `CommonUniformBuffer` in gfx_state.rs and is only ever extended at the end.
+//
+//   time        - seconds since the instance was created
+//   delta_time  - seconds since the previous frame
+//   frame       - index of the current frame, starting at 0
+//   seed        - random value in [0, 1), changes every frame, the sequence
+//                 repeats with a fixed timestep
+//   mouse       - xy: cursor position while the left button is down (pixels, origin bottom left)
+//                 zw: click position, z < 0 when released, w < 0 after the click frame
+//   resolution  - surface size in physical pixels
+//   pixel_ratio - physical pixels per logical pixel
+//   aspect      - resolution.x / resolution.y
+//   date        - year, month (0-11), day (1-31), seconds since midnight
+//                 (local time on the web, utc natively, 2000-01-01 plus time
+//                 with a fixed timestep)
+//   channel_resolution - per channel, xy: image size in pixels, z: 1 when an image is bound
+//   vertex_count   - vertices per instance drawn by the image pass, 3 unless set
+//   instance_count - instances drawn by the image pass, 1 unless set
+//   model       - centers the loaded mesh and scales it to fit the unit sphere,
+//                 identity without a mesh
+//   view, projection - orbit camera around the origin, dragging with the left
+//                 button rotates it and the wheel zooms while a mesh is loaded
+//   camera_position - xyz: camera position, w: 1 when a mesh is loaded
+struct CommonUniforms {
+  time: f32,
+  delta_time: f32,
+  frame: u32,
+  seed: f32,
+  mouse: vec4<f32>,
+  resolution: vec2<f32>,
+  pixel_ratio: f32,
+  aspect: f32,
+  date: vec4<f32>,
+  channel_resolution: array<vec4<f32>, 4>,
+  vertex_count: u32,
+  instance_count: u32,
+  model: mat4x4<f32>,
+  view: mat4x4<f32>,
+  projection: mat4x4<f32>,
+  camera_position: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> common_uniforms: CommonUniforms;
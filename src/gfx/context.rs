@@ -0,0 +1,324 @@
+use std::sync::{Arc, Mutex};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
+#[cfg(not(target_arch = "wasm32"))]
+use winit::window::Window;
+
+use crate::error::Error;
+use super::{gfx_state::COMMON_UNIFORMS_WGSL, mesh, shader};
+
+// the instance, adapter and device every view of an app renders with. views only own their
+// surface and what they draw, the built-in shader modules and the pipeline cache are shared
+#[derive(Debug)]
+pub struct GfxContext {
+  pub instance: wgpu::Instance,
+  pub adapter: wgpu::Adapter,
+  pub device: wgpu::Device,
+  pub queue: wgpu::Queue,
+  pub limits: wgpu::Limits,
+  // only where the backend supports it, e.g. vulkan
+  pub pipeline_cache: Option<wgpu::PipelineCache>,
+  pub fullscreen_module: wgpu::ShaderModule,
+  pub mesh_module: wgpu::ShaderModule,
+  // error scopes are a stack on the device, held from push to pop so a view only sees the
+  // errors of its own work
+  pub error_scopes: futures_util::lock::Mutex<()>,
+  // a webgl device can only present to the canvas it was created for, views draw there and
+  // copy the frame into their own canvas. a page keeps a single webgl context that way.
+  // `None` on webgpu, where every view presents to its own canvas
+  #[cfg(target_arch = "wasm32")]
+  pub canvas: Option<Arc<SharedCanvas>>,
+}
+
+impl GfxContext {
+  // picks an adapter that can present to `window`, its surface is returned for the first view
+  #[cfg(not(target_arch = "wasm32"))]
+  pub async fn new(window: Arc<Window>) -> Result<(Self, wgpu::Surface<'static>), Error> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+      backends: wgpu::Backends::PRIMARY,
+      ..Default::default()
+    });
+
+    let surface = instance.create_surface(window)?;
+
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: wgpu::PowerPreference::default(),
+      compatible_surface: Some(&surface),
+      force_fallback_adapter: false,
+    }).await.ok_or(Error::AdapterUnavailable)?;
+
+    // gl and other downlevel adapters can't meet the default limits
+    let limits = wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits());
+    let context = Self::from_adapter(instance, adapter, limits).await?;
+    Ok((context, surface))
+  }
+
+  // webgpu where the browser has it, webgl2 otherwise
+  #[cfg(target_arch = "wasm32")]
+  pub async fn new() -> Result<Self, Error> {
+    match Self::new_webgpu().await {
+      Ok(context) => Ok(context),
+      Err(err) => {
+        log::warn!("[gfx] webgpu unavailable, falling back to webgl2: {}", err);
+        Self::new_webgl().await
+      },
+    }
+  }
+
+  #[cfg(target_arch = "wasm32")]
+  async fn new_webgpu() -> Result<Self, Error> {
+    // without `navigator.gpu` this instance has no backend and finds no adapter
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+      backends: wgpu::Backends::BROWSER_WEBGPU,
+      ..Default::default()
+    });
+
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: wgpu::PowerPreference::default(),
+      compatible_surface: None,
+      force_fallback_adapter: false,
+    }).await.ok_or(Error::AdapterUnavailable)?;
+
+    let limits = adapter.limits();
+    Self::from_adapter(instance, adapter, limits, None).await
+  }
+
+  #[cfg(target_arch = "wasm32")]
+  async fn new_webgl() -> Result<Self, Error> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+      backends: wgpu::Backends::GL,
+      ..Default::default()
+    });
+
+    // never added to the document, frames are copied out of it
+    let element = web_sys::window()
+      .and_then(|win| win.document())
+      .and_then(|doc| doc.create_element("canvas").ok())
+      .and_then(|element| element.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+      .ok_or(Error::SurfaceUnsupported)?;
+    let surface = instance.create_surface(wgpu::SurfaceTarget::Canvas(element.clone()))?;
+
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: wgpu::PowerPreference::default(),
+      compatible_surface: Some(&surface),
+      force_fallback_adapter: false,
+    }).await.ok_or(Error::AdapterUnavailable)?;
+
+    let canvas = SharedCanvas {
+      element,
+      surface,
+      configured: Mutex::new(None),
+    };
+    Self::from_adapter(instance, adapter, wgpu::Limits::downlevel_webgl2_defaults(), Some(Arc::new(canvas))).await
+  }
+
+  // prefers the software adapter so this also works on machines without a gpu
+  #[cfg(not(target_arch = "wasm32"))]
+  pub async fn new_headless() -> Result<Self, Error> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+      backends: wgpu::Backends::all(),
+      ..Default::default()
+    });
+
+    let adapter = match instance.request_adapter(&wgpu::RequestAdapterOptions {
+      power_preference: wgpu::PowerPreference::default(),
+      compatible_surface: None,
+      force_fallback_adapter: true,
+    }).await {
+      Some(adapter) => adapter,
+      None => instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        compatible_surface: None,
+        force_fallback_adapter: false,
+      }).await.ok_or(Error::AdapterUnavailable)?,
+    };
+
+    log::info!("[gfx] headless adapter: {:?}", adapter.get_info());
+
+    let limits = wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits());
+    Self::from_adapter(instance, adapter, limits).await
+  }
+
+  async fn from_adapter(
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    limits: wgpu::Limits,
+    #[cfg(target_arch = "wasm32")] canvas: Option<Arc<SharedCanvas>>,
+  ) -> Result<Self, Error> {
+    let required_features = adapter.features() & wgpu::Features::PIPELINE_CACHE;
+
+    let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
+      label: None,
+      required_features,
+      required_limits: limits.clone(),
+      memory_hints: Default::default(),
+    }, None).await?;
+
+    device.on_uncaptured_error(Box::new(move |err| {
+      log::error!("[gfx] uncaptured error: {:?}", err);
+    }));
+
+    // safety: there's no previous cache data to validate
+    let pipeline_cache = required_features.contains(wgpu::Features::PIPELINE_CACHE).then(|| unsafe {
+      device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+        label: Some("pipeline cache"),
+        data: None,
+        fallback: true,
+      })
+    });
+
+    let fullscreen_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("fullscreen shader"),
+      source: wgpu::ShaderSource::Wgsl(shader::FULLSCREEN_WGSL.into()),
+    });
+    let mesh_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("mesh shader"),
+      source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", COMMON_UNIFORMS_WGSL, mesh::MESH_WGSL).into()),
+    });
+
+    log::info!("[gfx] backend: {:?}", adapter.get_info().backend);
+
+    Ok(Self {
+      instance,
+      adapter,
+      device,
+      queue,
+      limits,
+      pipeline_cache,
+      fullscreen_module,
+      mesh_module,
+      error_scopes: futures_util::lock::Mutex::new(()),
+      #[cfg(target_arch = "wasm32")]
+      canvas,
+    })
+  }
+
+  pub fn backend(&self) -> wgpu::Backend {
+    self.adapter.get_info().backend
+  }
+
+  // srgb if the surface offers it, sized to the window
+  pub fn surface_config(&self, surface: &wgpu::Surface, width: u32, height: u32) -> Result<wgpu::SurfaceConfiguration, Error> {
+    let surface_caps = surface.get_capabilities(&self.adapter);
+    let surface_format = surface_caps.formats
+      .iter()
+      .copied()
+      .find(|f| f.is_srgb())
+      .or(surface_caps.formats.first().copied())
+      .ok_or(Error::SurfaceUnsupported)?;
+
+    // webgpu canvases have no srgb formats, frames are drawn through an srgb view instead
+    let srgb_format = surface_format.add_srgb_suffix();
+    let view_formats = if srgb_format != surface_format
+      && self.adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS) {
+      vec![srgb_format]
+    } else {
+      vec![]
+    };
+
+    // frames are copied out of the surface for captures where it allows it
+    let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC);
+
+    Ok(wgpu::SurfaceConfiguration {
+      usage,
+      format: surface_format,
+      width,
+      height,
+      present_mode: surface_caps.present_modes.first().copied().unwrap_or_default(),
+      alpha_mode: surface_caps.alpha_modes.first().copied().unwrap_or_default(),
+      desired_maximum_frame_latency: 2,
+      view_formats,
+    })
+  }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+pub struct SharedCanvas {
+  pub element: web_sys::HtmlCanvasElement,
+  pub surface: wgpu::Surface<'static>,
+  // size the surface was last configured with, views of another size configure it again
+  configured: Mutex<Option<(u32, u32)>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl SharedCanvas {
+  // the next frame in the size of `config`
+  pub fn current_texture(&self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+    let mut configured = self.configured.lock().expect("[gfx] failed to lock canvas");
+    if *configured != Some((config.width, config.height)) {
+      self.surface.configure(device, config);
+      *configured = Some((config.width, config.height));
+    }
+    self.surface.get_current_texture()
+  }
+}
+
+// the context of an app, created for its first view and kept after the views are destroyed
+#[derive(Debug, Default)]
+pub struct SharedContext {
+  context: Option<Arc<GfxContext>>,
+  // views created while the first one still waits for its context, the senders are dropped
+  // when that fails and the next view tries again
+  #[cfg(target_arch = "wasm32")]
+  waiters: Option<Vec<futures_channel::oneshot::Sender<Arc<GfxContext>>>>,
+}
+
+impl SharedContext {
+  // the context and a surface for `window` created from it
+  #[cfg(not(target_arch = "wasm32"))]
+  pub async fn get_or_create(shared: &Mutex<SharedContext>, window: Arc<Window>) -> Result<(Arc<GfxContext>, wgpu::Surface<'static>), Error> {
+    let context = shared.lock().expect("[gfx] failed to lock context").context.clone();
+    if let Some(context) = context {
+      let surface = context.instance.create_surface(window)?;
+      return Ok((context, surface));
+    }
+
+    let (context, surface) = GfxContext::new(window).await?;
+    let context = Arc::new(context);
+    shared.lock().expect("[gfx] failed to lock context").context = Some(context.clone());
+    Ok((context, surface))
+  }
+
+  #[cfg(target_arch = "wasm32")]
+  pub async fn get_or_create(shared: &Mutex<SharedContext>) -> Result<Arc<GfxContext>, Error> {
+    loop {
+      let receiver = {
+        let mut shared = shared.lock().expect("[gfx] failed to lock context");
+        if let Some(context) = shared.context.as_ref() {
+          return Ok(context.clone());
+        }
+
+        match shared.waiters.as_mut() {
+          Some(waiters) => {
+            let (sender, receiver) = futures_channel::oneshot::channel();
+            waiters.push(sender);
+            Some(receiver)
+          },
+          None => {
+            shared.waiters = Some(Vec::new());
+            None
+          },
+        }
+      };
+
+      let Some(receiver) = receiver else {
+        let result = GfxContext::new().await.map(Arc::new);
+
+        let mut shared = shared.lock().expect("[gfx] failed to lock context");
+        let waiters = shared.waiters.take().unwrap_or_default();
+        if let Ok(context) = result.as_ref() {
+          shared.context = Some(context.clone());
+          for waiter in waiters {
+            let _ = waiter.send(context.clone());
+          }
+        }
+        return result;
+      };
+
+      if let Ok(context) = receiver.await {
+        return Ok(context);
+      }
+    }
+  }
+}
@@ -0,0 +1,311 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
+use crate::error::Error;
+use super::{capture::{self, CaptureError, FrameCapture}, playback::Playback};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+  #[default]
+  Png,
+  // tightly packed rgba8 rows, top row first, e.g. for `ffmpeg -f rawvideo -pix_fmt rgba`
+  Rgba,
+}
+
+impl ExportFormat {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "png" => Some(ExportFormat::Png),
+      "rgba" => Some(ExportFormat::Rgba),
+      _ => None,
+    }
+  }
+}
+
+// frames at `start`, `start + 1 / fps`, ... up to and including `end`, rendered with a fixed
+// timestep from empty buffer passes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportDesc {
+  pub start: f32,
+  pub end: f32,
+  pub fps: f32,
+  // the current render size when `None`
+  pub size: Option<(u32, u32)>,
+}
+
+impl ExportDesc {
+  pub fn validate(&self, max_dimension: u32) -> Result<(), Error> {
+    if !self.start.is_finite() || !self.end.is_finite() || self.end < self.start {
+      return Err(Error::InvalidParameter(format!("export end must not be before its start, got {} to {}", self.start, self.end)));
+    }
+    if !self.fps.is_finite() || self.fps <= 0.0 {
+      return Err(Error::InvalidParameter(format!("export fps must be positive, got {}", self.fps)));
+    }
+    if let Some((width, height)) = self.size {
+      if width == 0 || height == 0 || width > max_dimension || height > max_dimension {
+        return Err(Error::InvalidParameter(format!("export size must be between 1x1 and {0}x{0}, got {1}x{2}", max_dimension, width, height)));
+      }
+    }
+    Ok(())
+  }
+
+  // gifs are also limited in size and frame rate
+  pub fn validate_gif(&self) -> Result<(), Error> {
+    self.validate(u16::MAX as u32)?;
+    if self.fps > GIF_MAX_FPS {
+      return Err(Error::InvalidParameter(format!("gif fps must be at most {}, got {}", GIF_MAX_FPS, self.fps)));
+    }
+    Ok(())
+  }
+
+  pub fn frame_count(&self) -> u32 {
+    // the epsilon keeps `end` when the range is a whole number of frames
+    ((self.end - self.start) * self.fps + 1e-3).floor() as u32 + 1
+  }
+
+  pub fn frame_time(&self, index: u32) -> f32 {
+    self.start + index as f32 / self.fps
+  }
+
+  // drops the frame at `end`, a looping animation would otherwise show the first frame twice
+  pub fn looped(self) -> Self {
+    Self {
+      end: (self.end - 1.0 / self.fps).max(self.start),
+      ..self
+    }
+  }
+}
+
+// default size of gifs, the longer side of the render size is scaled down to this
+pub const GIF_MAX_SIZE: u32 = 320;
+
+// gif delays are in hundredths of a second and browsers slow down anything under 2
+pub const GIF_MAX_FPS: f32 = 50.0;
+
+// scales `size` down so neither side exceeds `max`, keeping the aspect ratio
+pub fn fit_size(size: (u32, u32), max: u32) -> (u32, u32) {
+  let (width, height) = size;
+  let scale = (max as f32 / width.max(height) as f32).min(1.0);
+  (((width as f32 * scale).round() as u32).max(1), ((height as f32 * scale).round() as u32).max(1))
+}
+
+#[derive(Debug)]
+pub struct ExportFrame {
+  pub index: u32,
+  pub frame_count: u32,
+  pub time: f32,
+  pub width: u32,
+  pub height: u32,
+  // png bytes or raw rgba, depending on the format
+  pub data: Vec<u8>,
+}
+
+// what `GfxState::end_export` restores
+#[derive(Debug)]
+pub struct ExportState {
+  pub desc: ExportDesc,
+  pub texture: wgpu::Texture,
+  pub cancel: Arc<AtomicBool>,
+  pub saved_size: winit::dpi::PhysicalSize<u32>,
+  pub saved_time: f32,
+  pub saved_frame: u32,
+  pub saved_rng_state: u32,
+  pub saved_playback: Playback,
+}
+
+// a looping animated gif, every frame gets its own palette
+pub struct GifEncoder {
+  encoder: gif::Encoder<Vec<u8>>,
+  fps: f32,
+  frame_count: u32,
+}
+
+impl GifEncoder {
+  pub fn new(width: u32, height: u32, fps: f32) -> Result<Self, Error> {
+    let (Ok(gif_width), Ok(gif_height)) = (u16::try_from(width), u16::try_from(height)) else {
+      return Err(Error::InvalidParameter(format!("gifs are at most 65535x65535, got {}x{}", width, height)));
+    };
+
+    let mut encoder = gif::Encoder::new(Vec::new(), gif_width, gif_height, &[]).map_err(CaptureError::EncodeGif)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(CaptureError::EncodeGif)?;
+
+    Ok(Self {
+      encoder,
+      fps,
+      frame_count: 0,
+    })
+  }
+
+  // in hundredths of a second, rounding is carried over to the next frame so the gif is as
+  // long as the exported range
+  fn delay(&self, index: u32) -> u16 {
+    let end = |index: u32| (index as f64 * 100.0 / self.fps as f64).round();
+    (end(index + 1) - end(index)).clamp(2.0, u16::MAX as f64) as u16
+  }
+
+  // quantizes an rgba frame, `pixels` is overwritten in the process
+  pub fn add_frame(&mut self, width: u32, height: u32, pixels: &mut [u8]) -> Result<(), Error> {
+    let mut frame = gif::Frame::from_rgba_speed(width as u16, height as u16, pixels, GIF_QUANTIZE_SPEED);
+    frame.delay = self.delay(self.frame_count);
+    self.encoder.write_frame(&frame).map_err(CaptureError::EncodeGif)?;
+    self.frame_count += 1;
+    Ok(())
+  }
+
+  pub fn finish(self) -> Result<Vec<u8>, Error> {
+    Ok(self.encoder.into_inner().map_err(CaptureError::Io)?)
+  }
+}
+
+// 1 is the best and slowest quantization, 30 the fastest
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+// renders every frame of `desc` into a gif, `on_progress` gets the frames done and the total
+pub async fn encode_gif(
+  desc: &ExportDesc,
+  render: impl FnMut(u32) -> Result<FrameCapture, Error>,
+  mut on_progress: impl FnMut(u32, u32),
+) -> Result<Vec<u8>, Error> {
+  let mut encoder: Option<GifEncoder> = None;
+
+  run(desc, ExportFormat::Rgba, render, |mut frame| {
+    let encoder = match encoder.as_mut() {
+      Some(encoder) => encoder,
+      None => encoder.insert(GifEncoder::new(frame.width, frame.height, desc.fps)?),
+    };
+    encoder.add_frame(frame.width, frame.height, &mut frame.data)?;
+    on_progress(frame.index + 1, frame.frame_count);
+    Ok(())
+  }).await?;
+
+  match encoder {
+    Some(encoder) => encoder.finish(),
+    None => Ok(Vec::new()),
+  }
+}
+
+// renders and reads back every frame in order. `render` starts frame `index`, it's called
+// again only after the previous readback finished so callers can release their locks
+// in between
+pub async fn run(
+  desc: &ExportDesc,
+  format: ExportFormat,
+  mut render: impl FnMut(u32) -> Result<FrameCapture, Error>,
+  mut on_frame: impl FnMut(ExportFrame) -> Result<(), Error>,
+) -> Result<u32, Error> {
+  let frame_count = desc.frame_count();
+
+  for index in 0..frame_count {
+    let (width, height, pixels) = render(index)?.into_rgba().await?;
+    let data = match format {
+      ExportFormat::Png => capture::encode_png(width, height, &pixels)?,
+      ExportFormat::Rgba => pixels,
+    };
+
+    on_frame(ExportFrame {
+      index,
+      frame_count,
+      time: desc.frame_time(index),
+      width,
+      height,
+      data,
+    })?;
+  }
+
+  Ok(frame_count)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn desc(start: f32, end: f32, fps: f32) -> ExportDesc {
+    ExportDesc { start, end, fps, size: None }
+  }
+
+  #[test]
+  fn counts_frames_including_the_end() {
+    assert_eq!(desc(0.0, 1.0, 30.0).frame_count(), 31);
+    assert_eq!(desc(2.0, 4.0, 24.0).frame_count(), 49);
+    // float error in `end * fps` must not drop the last frame
+    assert_eq!(desc(0.0, 0.7, 30.0).frame_count(), 22);
+    assert_eq!(desc(0.1, 0.3, 10.0).frame_count(), 3);
+  }
+
+  #[test]
+  fn counts_a_single_frame_for_an_empty_range() {
+    let desc = desc(1.5, 1.5, 60.0);
+    assert_eq!(desc.frame_count(), 1);
+    assert_eq!(desc.frame_time(0), 1.5);
+  }
+
+  #[test]
+  fn stops_before_a_fractional_end() {
+    // 2.4 frames in, the frame at 0.125 is past the end
+    let desc = desc(0.0, 0.1, 24.0);
+    assert_eq!(desc.frame_count(), 3);
+    assert!(desc.frame_time(desc.frame_count() - 1) <= desc.end);
+  }
+
+  #[test]
+  fn drops_the_last_frame_when_looped() {
+    assert_eq!(desc(0.0, 1.0, 30.0).looped().frame_count(), 30);
+    assert_eq!(desc(1.0, 1.0, 30.0).looped().frame_count(), 1);
+  }
+
+  #[test]
+  fn accepts_valid_ranges() {
+    assert!(desc(0.0, 0.0, 1.0).validate(4096).is_ok());
+    assert!(desc(-1.0, 2.0, 120.0).validate(4096).is_ok());
+    assert!(ExportDesc { size: Some((4096, 1)), ..desc(0.0, 1.0, 30.0) }.validate(4096).is_ok());
+  }
+
+  #[test]
+  fn rejects_invalid_ranges() {
+    assert!(desc(2.0, 1.0, 30.0).validate(4096).is_err());
+    assert!(desc(f32::NAN, 1.0, 30.0).validate(4096).is_err());
+    assert!(desc(0.0, f32::INFINITY, 30.0).validate(4096).is_err());
+    assert!(desc(0.0, 1.0, 0.0).validate(4096).is_err());
+    assert!(desc(0.0, 1.0, -30.0).validate(4096).is_err());
+    assert!(desc(0.0, 1.0, f32::NAN).validate(4096).is_err());
+    assert!(ExportDesc { size: Some((0, 64)), ..desc(0.0, 1.0, 30.0) }.validate(4096).is_err());
+    assert!(ExportDesc { size: Some((64, 4097)), ..desc(0.0, 1.0, 30.0) }.validate(4096).is_err());
+  }
+
+  // what a gif of `desc` lasts in hundredths of a second
+  fn gif_duration(desc: ExportDesc) -> u32 {
+    let desc = desc.looped();
+    let encoder = GifEncoder::new(1, 1, desc.fps).unwrap();
+    (0..desc.frame_count()).map(|index| encoder.delay(index) as u32).sum()
+  }
+
+  #[test]
+  fn gifs_last_as_long_as_the_range() {
+    for (start, end, fps) in [(0.0, 2.0, 30.0), (0.0, 1.0, 30.0), (1.0, 4.5, 30.0), (0.0, 2.0, 24.0), (0.5, 1.5, 24.0), (0.0, 3.0, 50.0)] {
+      let expected = ((end - start) * 100.0_f32).round() as u32;
+      assert_eq!(gif_duration(desc(start, end, fps)), expected, "{} to {} at {} fps", start, end, fps);
+    }
+  }
+
+  #[test]
+  fn carries_delay_rounding_to_the_next_frame() {
+    let encoder = GifEncoder::new(1, 1, 30.0).unwrap();
+    let delays: Vec<u16> = (0..6).map(|index| encoder.delay(index)).collect();
+    assert_eq!(delays, vec![3, 4, 3, 3, 4, 3]);
+  }
+
+  #[test]
+  fn limits_gif_fps() {
+    assert!(desc(0.0, 1.0, GIF_MAX_FPS).validate_gif().is_ok());
+    assert!(desc(0.0, 1.0, 24.0).validate_gif().is_ok());
+    assert!(desc(0.0, 1.0, 50.5).validate_gif().is_err());
+    assert!(desc(0.0, 1.0, 60.0).validate_gif().is_err());
+    // only gifs are limited
+    assert!(desc(0.0, 1.0, 60.0).validate(4096).is_ok());
+  }
+
+  #[test]
+  fn limits_gif_size() {
+    assert!(ExportDesc { size: Some((65535, 1)), ..desc(0.0, 1.0, 15.0) }.validate_gif().is_ok());
+    assert!(ExportDesc { size: Some((65536, 1)), ..desc(0.0, 1.0, 15.0) }.validate_gif().is_err());
+  }
+}
@@ -0,0 +1,21 @@
+// shaderx fragment inputs
+//
+// fragment-only wgsl shaders are drawn with the built-in fullscreen vertex stage,
+// `fs_main` can take this struct as its input. while a mesh is loaded the built-in
+// mesh vertex stage is used instead, which also writes `MeshInput`.
+//
+//   position - framebuffer position in pixels, origin top left
+//   uv       - 0..1 across the target, origin top left like texture coordinates
+struct FragmentInput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+//   normal         - unit normal after `model`
+//   world_position - position after `model`
+struct MeshInput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+  @location(1) normal: vec3<f32>,
+  @location(2) world_position: vec3<f32>,
+};
@@ -0,0 +1,14 @@
+// fullscreen triangle for `draw(0..3, 0..1)`, the output matches `FragmentInput`
+struct FullscreenOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> FullscreenOutput {
+  let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  var output: FullscreenOutput;
+  output.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+  output.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+  return output;
+}
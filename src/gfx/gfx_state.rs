@@ -1,34 +1,135 @@
-use std::sync::Arc;
-use wgpu::util::RenderEncoder;
+use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, Arc}};
 use winit::window::Window;
 
-use super::{pipeline::{Pipeline, PipelineCreateDesc}, uniform_buffer::{UniformBuffer, UniformBufferCreateDesc}};
+use crate::error::Error;
+use super::{
+  capture::{CaptureError, FrameCapture, FrameCaptureCreateDesc},
+  export::{ExportDesc, ExportState},
+  buffer_pass::{BufferBindings, BufferBindingsCreateDesc, BufferPass, BufferPassCreateDesc, PassTarget, MAX_BUFFER_PASSES},
+  camera::OrbitCamera,
+  channels::{Channel, ChannelBindings, ChannelCreateDesc, ChannelSamplerDesc, MAX_CHANNELS},
+  context::GfxContext,
+  mesh::{self, Mesh, MeshCreateDesc, MeshData},
+  parameters::{Parameter, ParameterKind},
+  pipeline::{DrawDesc, Pipeline},
+  pipeline_build::{BuildChange, BuiltPipelines, PassBuild, PipelineBuild, PipelineBuilder},
+  playback::Playback,
+  shader::{CompiledShader, EntryPoints, ShaderLanguage},
+  storage::{Storage, StorageCreateDesc, StorageUsage, MAX_STORAGE_BUFFERS, MAX_STORAGE_TEXTURES, STORAGE_TEXTURE_FORMAT},
+  uniform_buffer::{UniformBuffer, UniformBufferCreateDesc},
+  user_uniforms::{UniformLayout, UserUniformBindings, UserUniforms, USER_UNIFORMS_GROUP},
+};
+#[cfg(target_arch = "wasm32")]
+use super::context::SharedCanvas;
 use bytemuck::{Pod, Zeroable};
 use web_time::{SystemTime, UNIX_EPOCH, Duration};
 
+// wgsl declaration of `CommonUniformBuffer`, keep both in sync
+pub const COMMON_UNIFORMS_WGSL: &str = include_str!("common_uniforms.wgsl");
+
+// rng state used with a fixed timestep, must be odd
+const FIXED_SEED: u32 = 0x9e3779b9;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct CommonUniformBuffer {
   pub time: f32,
   pub delta_time: f32,
-  // padding
-  pub padding: [f32; 2],
+  pub frame: u32,
+  pub seed: f32,
+  // xy: current position, zw: click position (shadertoy iMouse)
+  pub mouse: [f32; 4],
+  pub resolution: [f32; 2],
+  pub pixel_ratio: f32,
+  pub aspect: f32,
+  // year, month (0-11), day (1-31), seconds since midnight
+  pub date: [f32; 4],
+  // xy: size in pixels, z: 1 when an image is bound
+  pub channel_resolution: [[f32; 4]; MAX_CHANNELS],
+  pub vertex_count: u32,
+  pub instance_count: u32,
+  // matrices are 16 byte aligned in wgsl
+  pub _padding: [u32; 2],
+  pub model: [[f32; 4]; 4],
+  pub view: [[f32; 4]; 4],
+  pub projection: [[f32; 4]; 4],
+  // xyz: camera position, w: 1 when a mesh is loaded
+  pub camera_position: [f32; 4],
+}
+
+#[derive(Debug)]
+enum RenderTarget {
+  Surface {
+    surface: wgpu::Surface<'static>,
+    window: Arc<Window>,
+  },
+  // drawn on the context's canvas and copied into the window's
+  #[cfg(target_arch = "wasm32")]
+  Canvas {
+    window: Arc<Window>,
+    canvas: Arc<SharedCanvas>,
+    context_2d: web_sys::CanvasRenderingContext2d,
+  },
+  // headless rendering into a texture of the configured size
+  Offscreen {
+    texture: wgpu::Texture,
+  },
+}
+
+impl RenderTarget {
+  fn scale_factor(&self) -> f64 {
+    match self {
+      RenderTarget::Surface { window, .. } => window.scale_factor(),
+      #[cfg(target_arch = "wasm32")]
+      RenderTarget::Canvas { window, .. } => window.scale_factor(),
+      RenderTarget::Offscreen { .. } => 1.0,
+    }
+  }
 }
 
 #[derive(Debug)]
 pub struct GfxState {
-  device: wgpu::Device,
-  queue: wgpu::Queue,
+  context: Arc<GfxContext>,
   config: wgpu::SurfaceConfiguration,
   size: winit::dpi::PhysicalSize<u32>,
-  surface: wgpu::Surface<'static>,
-  window: Arc<Window>,
-  limits: wgpu::Limits,
+  target: RenderTarget,
 
   surface_configured: bool,
+  // copy of the last frame drawn on a surface, which can't be read after `present`. `None`
+  // until a frame is drawn or when the surface can't be copied from
+  last_frame: Option<wgpu::Texture>,
   pipeline: Option<Pipeline>,
+  draw: DrawDesc,
+  // drawn by the image pass instead of `draw.vertex_count` vertices, with a depth buffer
+  // of the render size
+  mesh: Option<Mesh>,
+  depth_view: Option<wgpu::TextureView>,
+  camera: OrbitCamera,
+  // module each pass is drawn with, switching entry points only rebuilds the pipeline
+  shaders: HashMap<PassTarget, Arc<CompiledShader>>,
+  // what each pass's pipeline was built with besides its module, `set_draw` and
+  // `set_override` rebuild from them. overrides are by name
+  entry_points: HashMap<PassTarget, EntryPoints>,
+  overrides: HashMap<PassTarget, HashMap<String, f64>>,
+  // every pipeline is built with the same bind group layouts, compute ones bind storage
+  // writable in group 1
+  pipeline_layout: Arc<wgpu::PipelineLayout>,
+  compute_pipeline_layout: Option<Arc<wgpu::PipelineLayout>>,
+  buffers: [Option<BufferPass>; MAX_BUFFER_PASSES],
+  buffer_bindings: BufferBindings,
+  buffer_format: wgpu::TextureFormat,
+  // storage buffers and textures for compute passes, `None` when the device can't run them
+  storage: Option<Storage>,
+  channels: [Option<Channel>; MAX_CHANNELS],
+  channel_bindings: ChannelBindings,
+  user_uniforms: HashMap<PassTarget, UserUniforms>,
+  user_uniform_bindings: UserUniformBindings,
+  // live rendering is suspended while an export runs
+  export: Option<ExportState>,
 
   last_frame_time: Duration,
+  playback: Playback,
+  rng_state: u32,
   common_buffer_data: CommonUniformBuffer,
   common_buffer: UniformBuffer,
 
@@ -36,179 +137,1093 @@ pub struct GfxState {
 }
 
 impl GfxState {
-  pub async fn new(window: Arc<Window>) -> Self {
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn new(context: Arc<GfxContext>, window: Arc<Window>, surface: wgpu::Surface<'static>) -> Result<Self, Error> {
     let size = window.inner_size();
+    let config = context.surface_config(&surface, size.width, size.height)?;
 
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-      #[cfg(not(target_arch = "wasm32"))]
-        backends: wgpu::Backends::PRIMARY,
-      #[cfg(target_arch = "wasm32")]
-        backends: wgpu::Backends::GL,
-      ..Default::default()
-    });
+    Ok(Self::from_parts(context, config, RenderTarget::Surface { surface, window }))
+  }
 
-    let surface = instance.create_surface(window.clone()).expect("[gfx] failed to create surface");
+  // draws through the context's canvas on webgl, see `GfxContext::canvas`
+  #[cfg(target_arch = "wasm32")]
+  pub fn new(context: Arc<GfxContext>, window: Arc<Window>) -> Result<Self, Error> {
+    use wasm_bindgen::JsCast;
+    use winit::platform::web::WindowExtWebSys;
 
-    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
-      power_preference: wgpu::PowerPreference::default(),
-      compatible_surface: Some(&surface),
-      force_fallback_adapter: false,
-    }).await.expect("[gfx] failed to create adapter");
+    let size = window.inner_size();
 
-    let limits = {
-      #[cfg(not(target_arch = "wasm32"))] {
-        wgpu::Limits::default()
-      }
-      #[cfg(target_arch = "wasm32")] {
-        wgpu::Limits::downlevel_webgl2_defaults()
-      }
+    let Some(canvas) = context.canvas.clone() else {
+      let surface = context.instance.create_surface(window.clone())?;
+      let config = context.surface_config(&surface, size.width, size.height)?;
+      return Ok(Self::from_parts(context, config, RenderTarget::Surface { surface, window }));
     };
 
-    let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
-      label: None,
-      required_features: wgpu::Features::empty(),
-      required_limits: limits.clone(),
-      memory_hints: Default::default(),
-    }, None).await.expect("[gfx] failed to create device");
+    let config = context.surface_config(&canvas.surface, size.width, size.height)?;
+    let context_2d = window.canvas()
+      .and_then(|element| element.get_context("2d").ok().flatten())
+      .and_then(|context_2d| context_2d.dyn_into::<web_sys::CanvasRenderingContext2d>().ok())
+      .ok_or(Error::SurfaceUnsupported)?;
+    // frames replace the canvas, alpha included
+    let _ = context_2d.set_global_composite_operation("copy");
 
-    device.on_uncaptured_error(Box::new(move |err| {
-      log::error!("[gfx] uncaptured error: {:?}", err);
-    }));
+    Ok(Self::from_parts(context, config, RenderTarget::Canvas { window, canvas, context_2d }))
+  }
 
-    let surface_caps = surface.get_capabilities(&adapter);
-    let surface_format = surface_caps.formats
-      .iter()
-      .copied()
-      .find(|f| f.is_srgb())
-      .unwrap_or(surface_caps.formats[0]);
+  // renders into an offscreen texture, no window or display required
+  #[cfg(not(target_arch = "wasm32"))]
+  pub async fn new_headless(width: u32, height: u32) -> Result<Self, Error> {
+    let context = Arc::new(GfxContext::new_headless().await?);
+    Ok(Self::new_offscreen(context, width, height))
+  }
 
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn new_offscreen(context: Arc<GfxContext>, width: u32, height: u32) -> Self {
     let config = wgpu::SurfaceConfiguration {
-      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-      format: surface_format,
-      width: size.width,
-      height: size.height,
-      present_mode: surface_caps.present_modes[0],
-      alpha_mode: surface_caps.alpha_modes[0],
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+      format: wgpu::TextureFormat::Rgba8UnormSrgb,
+      width: std::cmp::min(width.max(1), context.limits.max_texture_dimension_2d),
+      height: std::cmp::min(height.max(1), context.limits.max_texture_dimension_2d),
+      present_mode: wgpu::PresentMode::Fifo,
+      alpha_mode: wgpu::CompositeAlphaMode::Opaque,
       desired_maximum_frame_latency: 2,
       view_formats: vec![],
     };
 
+    let texture = create_offscreen_texture(&context.device, &config);
+
+    let mut gfx = Self::from_parts(context, config, RenderTarget::Offscreen { texture });
+    gfx.surface_configured = true;
+    gfx
+  }
+
+  fn from_parts(context: Arc<GfxContext>, config: wgpu::SurfaceConfiguration, target: RenderTarget) -> Self {
+    let adapter = &context.adapter;
+    let device = &context.device;
+    let limits = &context.limits;
+
+    // prefer float buffers for feedback effects, webgl2 can't always render to them
+    let buffer_format = {
+      let features = adapter.get_texture_format_features(wgpu::TextureFormat::Rgba16Float);
+      if features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        && features.flags.contains(wgpu::TextureFormatFeatureFlags::FILTERABLE) {
+        wgpu::TextureFormat::Rgba16Float
+      } else {
+        wgpu::TextureFormat::Rgba8Unorm
+      }
+    };
+
     let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
 
     let common_buffer_data = CommonUniformBuffer {
       time: 0.0,
       delta_time: 0.0,
-      padding: [0.0; 2],
+      frame: 0,
+      seed: 0.0,
+      mouse: [0.0; 4],
+      resolution: [config.width as f32, config.height as f32],
+      pixel_ratio: target.scale_factor() as f32,
+      aspect: config.width as f32 / std::cmp::max(config.height, 1) as f32,
+      date: current_date(),
+      channel_resolution: [[0.0; 4]; MAX_CHANNELS],
+      vertex_count: DrawDesc::default().vertex_count,
+      instance_count: DrawDesc::default().instance_count,
+      _padding: [0; 2],
+      model: glam::Mat4::IDENTITY.to_cols_array_2d(),
+      view: glam::Mat4::IDENTITY.to_cols_array_2d(),
+      projection: glam::Mat4::IDENTITY.to_cols_array_2d(),
+      camera_position: [0.0; 4],
     };
 
     let common_buffer = UniformBuffer::new(&UniformBufferCreateDesc {
-      device: &device,
+      device,
       binding: 0,
       data: &common_buffer_data,
     });
 
-    Self {
+    // compute passes need compute shaders and storage in every stage that reads it, webgl2
+    // has neither
+    let downlevel = adapter.get_downlevel_capabilities();
+    let storage_supported = downlevel.flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::FRAGMENT_STORAGE)
+      && limits.max_storage_buffers_per_shader_stage >= MAX_STORAGE_BUFFERS as u32
+      && limits.max_storage_textures_per_shader_stage >= MAX_STORAGE_TEXTURES as u32
+      && adapter.get_texture_format_features(STORAGE_TEXTURE_FORMAT).allowed_usages.contains(wgpu::TextureUsages::STORAGE_BINDING);
+
+    let buffer_bindings = BufferBindings::new(&BufferBindingsCreateDesc {
+      device,
+      group: 1,
+      storage: storage_supported,
+      vertex_storage: downlevel.flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE),
+    });
+    let storage = storage_supported.then(|| Storage::new(&StorageCreateDesc {
       device,
-      queue,
-      config,
-      size,
-      surface,
-      window,
       limits,
+      width: config.width.max(1),
+      height: config.height.max(1),
+    }));
+    let channel_bindings = ChannelBindings::new(device, 2);
+    let user_uniform_bindings = UserUniformBindings::new(device, USER_UNIFORMS_GROUP);
+
+    let pipeline_layout = Arc::new(device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Pipeline Layout"),
+      bind_group_layouts: &[
+        &common_buffer.bind_group_layout,
+        &buffer_bindings.bind_group_layout,
+        &channel_bindings.bind_group_layout,
+        &user_uniform_bindings.bind_group_layout,
+      ],
+      push_constant_ranges: &[],
+    }));
+    let compute_pipeline_layout = buffer_bindings.compute_bind_group_layout.as_ref().map(|compute_layout| {
+      Arc::new(device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Compute Pipeline Layout"),
+        bind_group_layouts: &[
+          &common_buffer.bind_group_layout,
+          compute_layout,
+          &channel_bindings.bind_group_layout,
+          &user_uniform_bindings.bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+      }))
+    });
+
+    Self {
+      context,
+      size: winit::dpi::PhysicalSize::new(config.width, config.height),
+      config,
+      target,
       surface_configured: false,
+      last_frame: None,
       pipeline: None,
+      draw: DrawDesc::default(),
+      mesh: None,
+      depth_view: None,
+      camera: OrbitCamera::default(),
+      shaders: HashMap::new(),
+      entry_points: HashMap::new(),
+      overrides: HashMap::new(),
+      pipeline_layout,
+      compute_pipeline_layout,
+      buffers: Default::default(),
+      buffer_bindings,
+      buffer_format,
+      storage,
+      channels: Default::default(),
+      channel_bindings,
+      user_uniforms: HashMap::new(),
+      user_uniform_bindings,
+      export: None,
       common_buffer,
       common_buffer_data,
       last_frame_time: current_time,
+      playback: Playback::default(),
+      rng_state: (current_time.as_nanos() as u32) | 1,
       initialized: true,
     }
   }
 
   pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-    if !self.surface_configured || !self.initialized {
+    if !self.surface_configured || !self.initialized || self.export.is_some() {
       return Ok(());
     }
 
-    // update common buffer
     let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-    let delta_time = (current_time - self.last_frame_time).as_secs_f32();
+    let wall_delta = (current_time - self.last_frame_time).as_secs_f32();
     self.last_frame_time = current_time;
-    self.common_buffer_data.time += delta_time;
+
+    match self.playback.next_delta(wall_delta) {
+      Some(delta_time) => self.render_frame(delta_time),
+      None => self.redraw_frame(),
+    }
+  }
+
+  // renders a frame advancing time by `delta_time` instead of the wall clock
+  pub fn render_frame(&mut self, delta_time: f32) -> Result<(), wgpu::SurfaceError> {
+    if !self.surface_configured || !self.initialized {
+      return Ok(());
+    }
+
+    self.common_buffer_data.time = self.playback.wrap(self.common_buffer_data.time + delta_time);
     self.common_buffer_data.delta_time = delta_time;
-    self.common_buffer.update(&self.queue, &self.common_buffer_data);
+    self.common_buffer_data.seed = self.next_seed();
+    self.draw(true)?;
+    self.common_buffer_data.frame = self.common_buffer_data.frame.wrapping_add(1);
 
+    Ok(())
+  }
+
+  // while paused only the image pass is drawn again, so uniform and shader edits show up
+  // without running feedback buffers forward
+  fn redraw_frame(&mut self) -> Result<(), wgpu::SurfaceError> {
+    if !self.surface_configured || !self.initialized {
+      return Ok(());
+    }
+
+    self.common_buffer_data.delta_time = 0.0;
+    self.draw(false)
+  }
+
+  fn draw(&mut self, buffer_passes: bool) -> Result<(), wgpu::SurfaceError> {
     // setup render target
-    let output = self.surface.get_current_texture()?;
-    let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-    let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+    let (output, view) = match &self.target {
+      RenderTarget::Surface { surface, .. } => {
+        let output = surface.get_current_texture()?;
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+          format: Some(self.output_format()),
+          ..Default::default()
+        });
+        (Some(output), view)
+      },
+      #[cfg(target_arch = "wasm32")]
+      RenderTarget::Canvas { canvas, .. } => {
+        let output = canvas.current_texture(&self.context.device, &self.config)?;
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (Some(output), view)
+      },
+      RenderTarget::Offscreen { texture } => (None, texture.create_view(&wgpu::TextureViewDescriptor::default())),
+    };
+    let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
       label: Some("render encoder"),
     });
 
-    // render pass
-    {
-      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: Some("render pass"),
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-          view: &view,
-          resolve_target: None,
-          ops: wgpu::Operations {
-            load: wgpu::LoadOp::Clear(wgpu::Color::RED),
-            store: wgpu::StoreOp::Store,
-          },
-        })],
-        depth_stencil_attachment: None,
-        occlusion_query_set: None,
-        timestamp_writes: None,
-      });
-
-      render_pass.set_bind_group(self.common_buffer.binding, &self.common_buffer.bind_group, &[]);
-
-      if self.pipeline.is_some() {
-        let pipeline = self.pipeline.as_ref().unwrap();
-        render_pass.set_pipeline(&pipeline.pipeline);
-        render_pass.draw(0..3, 0..1);
-      }
+    self.encode_frame(&mut encoder, &view, buffer_passes);
+    if let Some(output) = output.as_ref() {
+      self.copy_last_frame(&mut encoder, &output.texture);
     }
 
     // submit
-    self.queue.submit(std::iter::once(encoder.finish()));
-    output.present();
+    self.context.queue.submit(std::iter::once(encoder.finish()));
+    if let Some(output) = output {
+      output.present();
+    }
+    #[cfg(target_arch = "wasm32")]
+    if let RenderTarget::Canvas { canvas, context_2d, .. } = &self.target {
+      let _ = context_2d.draw_image_with_html_canvas_element(&canvas.element, 0.0, 0.0);
+    }
 
     Ok(())
   }
 
+  // uploads the uniforms and records the buffer passes and the image pass into `view`
+  fn encode_frame(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, buffer_passes: bool) {
+    // update common buffer, the date follows shader time when the timestep is fixed
+    self.common_buffer_data.date = match self.playback.fixed_timestep {
+      Some(_) => fixed_date(self.common_buffer_data.time),
+      None => current_date(),
+    };
+    self.update_camera_uniforms();
+    self.common_buffer.update(&self.context.queue, &self.common_buffer_data);
+    for uniforms in self.user_uniforms.values_mut() {
+      uniforms.upload(&self.context.queue);
+    }
+
+    // buffer passes, in order, each one sees the buffers rendered before it
+    for i in 0..MAX_BUFFER_PASSES {
+      if !buffer_passes || self.buffers[i].is_none() {
+        continue;
+      }
+
+      let bind_group = self.buffer_bindings.create_bind_group(&self.context.device, &self.buffers, self.storage.as_ref());
+      let buffer = self.buffers[i].as_mut().unwrap();
+
+      {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+          label: Some("buffer pass"),
+          color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: buffer.write_view(),
+            resolve_target: None,
+            ops: wgpu::Operations {
+              load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+              store: wgpu::StoreOp::Store,
+            },
+          })],
+          depth_stencil_attachment: None,
+          occlusion_query_set: None,
+          timestamp_writes: None,
+        });
+
+        render_pass.set_bind_group(self.common_buffer.binding, &self.common_buffer.bind_group, &[]);
+        render_pass.set_bind_group(self.buffer_bindings.group, &bind_group, &[]);
+        render_pass.set_bind_group(self.channel_bindings.group, &self.channel_bindings.bind_group, &[]);
+        render_pass.set_bind_group(self.user_uniform_bindings.group, self.user_uniform_bindings.bind_group(self.user_uniforms.get(&PassTarget::Buffer(i))), &[]);
+        render_pass.set_pipeline(&buffer.pipeline.pipeline);
+        render_pass.draw(0..3, 0..1);
+      }
+
+      buffer.swap();
+    }
+
+    // compute passes see this frame's buffers and write what the image pass reads
+    if buffer_passes {
+      self.encode_compute_passes(encoder);
+    }
+
+    // render pass
+    self.encode_image_pass(encoder, view);
+  }
+
+  // compute entry points of the buffer passes in order, then the ones of the image pass
+  fn encode_compute_passes(&self, encoder: &mut wgpu::CommandEncoder) {
+    let Some(storage) = self.storage.as_ref() else {
+      return;
+    };
+
+    let pipelines: Vec<(PassTarget, &Pipeline)> = self.buffers.iter().enumerate()
+      .filter_map(|(i, buffer)| buffer.as_ref().map(|buffer| (PassTarget::Buffer(i), &buffer.pipeline)))
+      .chain(self.pipeline.as_ref().map(|pipeline| (PassTarget::Image, pipeline)))
+      .filter(|(_, pipeline)| !pipeline.compute.is_empty())
+      .collect();
+    if pipelines.is_empty() {
+      return;
+    }
+
+    let Some(bind_group) = self.buffer_bindings.create_compute_bind_group(&self.context.device, &self.buffers, storage) else {
+      return;
+    };
+
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+      label: Some("compute pass"),
+      timestamp_writes: None,
+    });
+
+    compute_pass.set_bind_group(self.common_buffer.binding, &self.common_buffer.bind_group, &[]);
+    compute_pass.set_bind_group(self.buffer_bindings.group, &bind_group, &[]);
+    compute_pass.set_bind_group(self.channel_bindings.group, &self.channel_bindings.bind_group, &[]);
+
+    let max_workgroups = self.context.limits.max_compute_workgroups_per_dimension;
+    for (pass, pipeline) in pipelines {
+      compute_pass.set_bind_group(self.user_uniform_bindings.group, self.user_uniform_bindings.bind_group(self.user_uniforms.get(&pass)), &[]);
+
+      for compute in pipeline.compute.iter() {
+        let [x, y, z] = compute.workgroups(self.config.width, self.config.height).map(|count| count.min(max_workgroups));
+        compute_pass.set_pipeline(&compute.pipeline);
+        compute_pass.dispatch_workgroups(x, y, z);
+      }
+    }
+  }
+
+  fn encode_image_pass(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+    let buffer_bind_group = self.buffer_bindings.create_bind_group(&self.context.device, &self.buffers, self.storage.as_ref());
+
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("render pass"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Clear(self.draw.clear_color),
+          store: wgpu::StoreOp::Store,
+        },
+      })],
+      depth_stencil_attachment: self.depth_view.as_ref().map(|view| wgpu::RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: Some(wgpu::Operations {
+          load: wgpu::LoadOp::Clear(1.0),
+          store: wgpu::StoreOp::Discard,
+        }),
+        stencil_ops: None,
+      }),
+      occlusion_query_set: None,
+      timestamp_writes: None,
+    });
+
+    render_pass.set_bind_group(self.common_buffer.binding, &self.common_buffer.bind_group, &[]);
+    render_pass.set_bind_group(self.buffer_bindings.group, &buffer_bind_group, &[]);
+    render_pass.set_bind_group(self.channel_bindings.group, &self.channel_bindings.bind_group, &[]);
+    render_pass.set_bind_group(self.user_uniform_bindings.group, self.user_uniform_bindings.bind_group(self.user_uniforms.get(&PassTarget::Image)), &[]);
+
+    if let Some(pipeline) = self.pipeline.as_ref() {
+      render_pass.set_pipeline(&pipeline.pipeline);
+      match self.mesh.as_ref() {
+        Some(mesh) => {
+          render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+          render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+          render_pass.draw_indexed(0..mesh.index_count, 0, 0..self.draw.instance_count);
+        },
+        None => render_pass.draw(0..self.draw.vertex_count, 0..self.draw.instance_count),
+      }
+    }
+  }
+
+  // reads the last rendered frame back as png
+  pub async fn capture_frame(&self) -> Result<Vec<u8>, Error> {
+    Ok(self.begin_capture().into_png().await?)
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub async fn save_frame(&self, path: &std::path::Path) -> Result<(), Error> {
+    let png = self.capture_frame().await?;
+    std::fs::write(path, png).map_err(|err| Error::Capture(CaptureError::Io(err)))
+  }
+
+  // records and submits the copy of the last rendered frame, the returned capture can be
+  // awaited without borrowing self
+  pub fn begin_capture(&self) -> FrameCapture {
+    let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("capture encoder"),
+    });
+
+    let rendered;
+    let texture = match (&self.target, self.last_frame.as_ref()) {
+      (RenderTarget::Offscreen { texture }, _) => texture,
+      (_, Some(texture)) => texture,
+      // nothing to copy, the image pass is drawn again with the current uniforms
+      _ => {
+        rendered = create_capture_texture(&self.context.device, self.output_format(), self.config.width, self.config.height);
+        self.encode_image_pass(&mut encoder, &rendered.create_view(&wgpu::TextureViewDescriptor::default()));
+        &rendered
+      },
+    };
+
+    let mut capture = FrameCapture::new(FrameCaptureCreateDesc {
+      device: &self.context.device,
+      encoder: &mut encoder,
+      texture,
+      opaque: self.config.alpha_mode == wgpu::CompositeAlphaMode::Opaque,
+    });
+
+    self.context.queue.submit(std::iter::once(encoder.finish()));
+    capture.map(&self.context.device);
+    capture
+  }
+
+  // keeps a copy of the surface texture for `begin_capture`
+  fn copy_last_frame(&mut self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+    if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+      return;
+    }
+
+    let size = texture.size();
+    if !self.last_frame.as_ref().is_some_and(|last_frame| last_frame.size() == size && last_frame.format() == texture.format()) {
+      self.last_frame = Some(create_capture_texture(&self.context.device, texture.format(), size.width, size.height));
+    }
+    if let Some(last_frame) = self.last_frame.as_ref() {
+      encoder.copy_texture_to_texture(texture.as_image_copy(), last_frame.as_image_copy(), size);
+    }
+  }
+
   pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
     if new_size.width > 0 && new_size.height > 0 {
+      // applied when the export ends
+      if let Some(export) = self.export.as_mut() {
+        export.saved_size = new_size;
+        return;
+      }
+
       self.size = new_size;
-      self.config.width = std::cmp::min(new_size.width, self.limits.max_texture_dimension_2d);
-      self.config.height = std::cmp::min(new_size.height, self.limits.max_texture_dimension_2d);
-      self.surface.configure(&self.device, &self.config);
+      self.set_render_size(new_size.width, new_size.height);
+      match &mut self.target {
+        RenderTarget::Surface { surface, .. } => surface.configure(&self.context.device, &self.config),
+        // the context's canvas is configured when drawing
+        #[cfg(target_arch = "wasm32")]
+        RenderTarget::Canvas { context_2d, .. } => if let Some(canvas) = context_2d.canvas() {
+          canvas.set_width(self.config.width);
+          canvas.set_height(self.config.height);
+        },
+        RenderTarget::Offscreen { texture } => *texture = create_offscreen_texture(&self.context.device, &self.config),
+      }
       self.surface_configured = true;
     }
   }
 
-  pub fn update_shader(&mut self, shader_source: &str) {
-    self.pipeline = Some(Pipeline::new(&PipelineCreateDesc {
-      device: &self.device,
-      config: &self.config,
-      shader_source,
-      bind_group_layouts: &vec![&self.common_buffer.bind_group_layout],
-    }));
+  // size of the buffer passes and `resolution`, the surface is configured separately
+  fn set_render_size(&mut self, width: u32, height: u32) {
+    self.config.width = std::cmp::min(width, self.context.limits.max_texture_dimension_2d);
+    self.config.height = std::cmp::min(height, self.context.limits.max_texture_dimension_2d);
+
+    for buffer in self.buffers.iter_mut().flatten() {
+      buffer.resize(&self.context.device, self.config.width, self.config.height);
+    }
+    if let Some(storage) = self.storage.as_mut() {
+      storage.resize(&self.context.device, self.config.width, self.config.height);
+    }
+    if self.depth_view.is_some() {
+      self.depth_view = Some(mesh::create_depth_view(&self.context.device, self.config.width, self.config.height));
+    }
+
+    self.common_buffer_data.resolution = [self.config.width as f32, self.config.height as f32];
+    self.common_buffer_data.pixel_ratio = self.target.scale_factor() as f32;
+    self.common_buffer_data.aspect = self.config.width as f32 / self.config.height as f32;
   }
 
-  pub async fn compile_shader(&self, shader_source: &str) -> wgpu::CompilationInfo {
-    let shader_module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-      label: Some("temp shader"),
-      source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+  // switches to the export size and timestep until `end_export`, the returned flag cancels
+  // the export when set. buffer passes start empty and are reset again afterwards
+  pub fn begin_export(&mut self, desc: ExportDesc) -> Result<Arc<AtomicBool>, Error> {
+    if self.export.is_some() {
+      return Err(Error::ExportInProgress);
+    }
+    desc.validate(self.context.limits.max_texture_dimension_2d)?;
+    // nothing may fail once the export state is set
+    let mut playback = Playback::default();
+    playback.set_fixed_fps(Some(desc.fps))?;
+
+    let (width, height) = desc.size.unwrap_or((self.config.width, self.config.height));
+    let cancel = Arc::new(AtomicBool::new(false));
+    self.export = Some(ExportState {
+      desc,
+      texture: create_capture_texture(&self.context.device, self.output_format(), width, height),
+      cancel: cancel.clone(),
+      saved_size: self.size,
+      saved_time: self.common_buffer_data.time,
+      saved_frame: self.common_buffer_data.frame,
+      saved_rng_state: self.rng_state,
+      saved_playback: std::mem::replace(&mut self.playback, playback),
+    });
+
+    self.set_render_size(width, height);
+    self.restart();
+    Ok(cancel)
+  }
+
+  // renders frame `index` of the export and starts reading it back
+  pub fn render_export_frame(&mut self, index: u32) -> Result<FrameCapture, Error> {
+    let Some(export) = self.export.as_ref() else {
+      return Err(Error::InvalidParameter(String::from("no export in progress")));
+    };
+    if export.cancel.load(Ordering::Relaxed) {
+      return Err(Error::ExportCancelled);
+    }
+
+    let desc = export.desc;
+    let view = export.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    self.common_buffer_data.time = desc.frame_time(index);
+    self.common_buffer_data.delta_time = 1.0 / desc.fps;
+    self.common_buffer_data.frame = index;
+    self.common_buffer_data.seed = self.next_seed();
+
+    let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("export encoder"),
     });
+    self.encode_frame(&mut encoder, &view, true);
 
-    let result = shader_module.get_compilation_info().await;
-    log::info!("{:?}", result);
+    let mut capture = FrameCapture::new(FrameCaptureCreateDesc {
+      device: &self.context.device,
+      encoder: &mut encoder,
+      texture: &self.export.as_ref().unwrap().texture,
+      opaque: self.config.alpha_mode == wgpu::CompositeAlphaMode::Opaque,
+    });
 
-    result
+    self.context.queue.submit(std::iter::once(encoder.finish()));
+    capture.map(&self.context.device);
+    Ok(capture)
+  }
+
+  // format the image pass renders in, the srgb view of the surface format where there is one
+  fn output_format(&self) -> wgpu::TextureFormat {
+    self.config.view_formats.first().copied().unwrap_or(self.config.format)
+  }
+
+  pub fn render_size(&self) -> (u32, u32) {
+    (self.config.width, self.config.height)
+  }
+
+  pub fn cancel_export(&self) {
+    if let Some(export) = self.export.as_ref() {
+      export.cancel.store(true, Ordering::Relaxed);
+    }
+  }
+
+  // restores the size, time and playback from before the export
+  pub fn end_export(&mut self) {
+    let Some(export) = self.export.take() else {
+      return;
+    };
+
+    self.playback = export.saved_playback;
+    self.rng_state = export.saved_rng_state;
+    self.common_buffer_data.time = export.saved_time;
+    self.common_buffer_data.frame = export.saved_frame;
+    // a minimized window keeps its surface until the next resize
+    if export.saved_size.width > 0 && export.saved_size.height > 0 {
+      self.resize(export.saved_size);
+    } else {
+      self.set_render_size(self.size.width.max(1), self.size.height.max(1));
+    }
+  }
+
+  pub fn set_time(&mut self, time: f32) {
+    self.common_buffer_data.time = time;
+  }
+
+  pub fn time(&self) -> f32 {
+    self.common_buffer_data.time
+  }
+
+  pub fn frame(&self) -> u32 {
+    self.common_buffer_data.frame
+  }
+
+  pub fn playback(&self) -> &Playback {
+    &self.playback
+  }
+
+  pub fn playback_mut(&mut self) -> &mut Playback {
+    &mut self.playback
+  }
+
+  // advances time by exactly 1 / `fps` per frame and makes the seed and date reproducible,
+  // `restart` afterwards to get the same frames as another run
+  pub fn set_fixed_fps(&mut self, fps: Option<f32>) -> Result<(), Error> {
+    self.playback.set_fixed_fps(fps)?;
+    if fps.is_some() {
+      self.rng_state = FIXED_SEED;
+    }
+    Ok(())
+  }
+
+  // back to time and frame 0 with empty buffer passes and zeroed storage
+  pub fn restart(&mut self) {
+    self.common_buffer_data.time = 0.0;
+    self.common_buffer_data.delta_time = 0.0;
+    self.common_buffer_data.frame = 0;
+    if self.playback.fixed_timestep.is_some() {
+      self.rng_state = FIXED_SEED;
+    }
+
+    for buffer in self.buffers.iter_mut().flatten() {
+      buffer.resize(&self.context.device, self.config.width.max(1), self.config.height.max(1));
+    }
+    if let Some(storage) = self.storage.as_mut() {
+      storage.clear(&self.context.device);
+    }
+  }
+
+  pub fn set_mouse(&mut self, mouse: [f32; 4]) {
+    self.common_buffer_data.mouse = mouse;
+  }
+
+  // keeps the current pipeline of `pass` when the new one fails to build
+  pub async fn update_shader(&mut self, pass: PassTarget, shader_source: &str, language: ShaderLanguage, entry_points: &EntryPoints) -> Result<(), Error> {
+    let built = self.begin_update_shader(pass, shader_source, language, entry_points).run().await?;
+    self.finish_build(built);
+    Ok(())
+  }
+
+  // the module of `pass` is only compiled again when the source changed, switching entry
+  // points just builds another pipeline
+  pub fn begin_update_shader(&self, pass: PassTarget, shader_source: &str, language: ShaderLanguage, entry_points: &EntryPoints) -> PipelineBuild {
+    let shader = self.shaders.get(&pass).filter(|shader| shader.matches(shader_source, language, pass == PassTarget::Image));
+    PipelineBuild {
+      builder: self.pipeline_builder(),
+      passes: vec![PassBuild {
+        pass,
+        source: shader_source.to_string(),
+        language,
+        shader: shader.cloned(),
+        entry_points: entry_points.clone(),
+        overrides: self.overrides.get(&pass).cloned().unwrap_or_default(),
+      }],
+      change: BuildChange::Shader,
+    }
+  }
+
+  // builds the new pipelines of a `begin_*` method without borrowing `self` while they build.
+  // hand the result to `finish_build`
+  fn pipeline_builder(&self) -> PipelineBuilder {
+    PipelineBuilder {
+      context: self.context.clone(),
+      layout: self.pipeline_layout.clone(),
+      compute_layout: self.compute_pipeline_layout.clone(),
+      storage_group: self.buffer_bindings.group,
+      output_format: self.output_format(),
+      buffer_format: self.buffer_format,
+      draw: self.draw,
+      mesh: self.mesh.is_some(),
+    }
+  }
+
+  // `pass` as it's built now, `None` when nothing was built for it
+  fn current_pass_build(&self, pass: PassTarget) -> Option<PassBuild> {
+    let shader = self.shaders.get(&pass)?;
+    Some(PassBuild {
+      pass,
+      source: shader.source.clone(),
+      language: shader.language,
+      shader: Some(shader.clone()),
+      entry_points: self.entry_points.get(&pass).cloned().unwrap_or_default(),
+      overrides: self.overrides.get(&pass).cloned().unwrap_or_default(),
+    })
+  }
+
+  // applies what a `begin_*` method built
+  pub fn finish_build(&mut self, built: BuiltPipelines) {
+    match built.change {
+      BuildChange::Shader => {},
+      BuildChange::Draw(desc) => {
+        self.draw = desc;
+        self.common_buffer_data.vertex_count = desc.vertex_count;
+        self.common_buffer_data.instance_count = desc.instance_count;
+      },
+      BuildChange::Mesh(mesh) => {
+        let mesh = mesh.map(|mesh| *mesh);
+        self.depth_view = mesh.is_some().then(|| mesh::create_depth_view(&self.context.device, self.config.width.max(1), self.config.height.max(1)));
+        self.mesh = mesh;
+      },
+    }
+
+    for built in built.passes {
+      self.shaders.insert(built.pass, built.shader);
+      self.entry_points.insert(built.pass, built.entry_points);
+      self.overrides.insert(built.pass, built.overrides);
+      self.update_user_uniforms(built.pass);
+      self.set_pass_pipeline(built.pass, built.pipeline);
+    }
+    self.update_storage();
+  }
+
+  fn set_pass_pipeline(&mut self, pass: PassTarget, pipeline: Pipeline) {
+    match pass {
+      PassTarget::Image => self.pipeline = Some(pipeline),
+      PassTarget::Buffer(index) => match self.buffers[index].as_mut() {
+        Some(buffer) => buffer.pipeline = pipeline,
+        None => {
+          self.buffers[index] = Some(BufferPass::new(BufferPassCreateDesc {
+            device: &self.context.device,
+            pipeline,
+            format: self.buffer_format,
+            width: self.config.width.max(1),
+            height: self.config.height.max(1),
+          }));
+        },
+      },
+    }
+  }
+
+  // sets the `override` constant `name` in every pass that declares it and rebuilds their
+  // pipelines, nothing changes when one of them fails to build
+  pub async fn set_override(&mut self, name: &str, value: f64) -> Result<(), Error> {
+    let built = self.begin_set_override(name, value)?.run().await?;
+    self.finish_build(built);
+    Ok(())
+  }
+
+  pub fn begin_set_override(&self, name: &str, value: f64) -> Result<PipelineBuild, Error> {
+    if !value.is_finite() {
+      return Err(Error::InvalidParameter(format!("override `{}` must be a finite number, got {}", name, value)));
+    }
+
+    let passes: Vec<PassBuild> = self.shaders.iter()
+      .filter(|(_, shader)| shader.overrides.contains_key(name))
+      .filter_map(|(pass, _)| self.current_pass_build(*pass))
+      .map(|mut build| {
+        // from a new module, gl caches programs by module and entry point whatever the constants
+        build.shader = None;
+        build.overrides.insert(name.to_string(), value);
+        build
+      })
+      .collect();
+    if passes.is_empty() {
+      return Err(Error::InvalidParameter(format!("no shader declares the override `{}`", name)));
+    }
+
+    Ok(PipelineBuild {
+      builder: self.pipeline_builder(),
+      passes,
+      change: BuildChange::Shader,
+    })
+  }
+
+  // storage grows to what the compiled passes declare
+  fn update_storage(&mut self) {
+    let Some(storage) = self.storage.as_mut() else {
+      return;
+    };
+
+    let mut usage = StorageUsage::default();
+    for shader in self.shaders.values() {
+      usage.merge(&shader.storage);
+    }
+    storage.set_usage(&self.context.device, usage);
+  }
+
+  pub fn supports_compute(&self) -> bool {
+    self.storage.is_some()
+  }
+
+  pub fn backend(&self) -> wgpu::Backend {
+    self.context.backend()
+  }
+
+  pub fn limits(&self) -> &wgpu::Limits {
+    &self.context.limits
+  }
+
+  // bytes of storage buffer `index`, `None` sizes it to what the shaders declare. the buffer
+  // is cleared when its size changes
+  pub fn set_storage_buffer_size(&mut self, index: usize, size: Option<u64>) -> Result<(), Error> {
+    let storage = self.storage.as_mut().ok_or_else(|| Error::Unsupported(String::from("storage buffers are")))?;
+    storage.set_buffer_size(&self.context.device, index, size)
+  }
+
+  // `None` makes storage texture `index` follow the render size
+  pub fn set_storage_texture_size(&mut self, index: usize, size: Option<(u32, u32)>) -> Result<(), Error> {
+    let storage = self.storage.as_mut().ok_or_else(|| Error::Unsupported(String::from("storage textures are")))?;
+    storage.set_texture_size(&self.context.device, index, size)
+  }
+
+  // recreates the uniform buffer of `pass` when its layout changed. new fields start at their
+  // `@default`, values of fields that are still there are kept so recompiling doesn't reset them
+  fn update_user_uniforms(&mut self, pass: PassTarget) {
+    let shader = &self.shaders[&pass];
+    let Some(layout) = shader.uniform_layout.clone() else {
+      self.user_uniforms.remove(&pass);
+      return;
+    };
+
+    if self.user_uniforms.get(&pass).is_some_and(|uniforms| uniforms.layout == layout) {
+      return;
+    }
+
+    let mut uniforms = UserUniforms::new(&self.context.device, &self.user_uniform_bindings, layout);
+    for parameter in shader.parameters.iter().filter(|parameter| parameter.kind == ParameterKind::Uniform) {
+      if let Some(default) = parameter.default.as_ref() {
+        let _ = uniforms.set(&parameter.name, default);
+      }
+    }
+    if let Some(previous) = self.user_uniforms.get(&pass) {
+      uniforms.copy_values(previous);
+    }
+    self.user_uniforms.insert(pass, uniforms);
+  }
+
+  // sets `name` in every pass that declares it, matrices are column major
+  pub fn set_uniform(&mut self, name: &str, values: &[f64]) -> Result<(), Error> {
+    let mut found = false;
+    for uniforms in self.user_uniforms.values_mut() {
+      found |= uniforms.set(name, values)?;
+    }
+
+    if !found {
+      return Err(Error::InvalidParameter(format!("no shader declares the uniform `{}`", name)));
+    }
+    Ok(())
+  }
+
+  pub fn uniform_layout(&self, pass: PassTarget) -> Option<&UniformLayout> {
+    self.user_uniforms.get(&pass).map(|uniforms| &uniforms.layout)
+  }
+
+  // annotated parameters of the shader currently compiled for `pass`
+  pub fn parameters(&self, pass: PassTarget) -> &[Parameter] {
+    self.shaders.get(&pass).map(|shader| shader.parameters.as_slice()).unwrap_or_default()
+  }
+
+  pub fn remove_pass(&mut self, pass: PassTarget) {
+    match pass {
+      PassTarget::Image => self.pipeline = None,
+      PassTarget::Buffer(index) => self.buffers[index] = None,
+    }
+    self.shaders.remove(&pass);
+    self.entry_points.remove(&pass);
+    self.overrides.remove(&pass);
+    self.user_uniforms.remove(&pass);
+    self.update_storage();
+  }
+
+  // rebuilds the image pipeline when the topology, culling or blending changed, the previous
+  // settings are kept when that fails
+  pub async fn set_draw(&mut self, desc: DrawDesc) -> Result<(), Error> {
+    let built = self.begin_set_draw(desc)?.run().await?;
+    self.finish_build(built);
+    Ok(())
+  }
+
+  // the image pipeline bakes in the draw settings and whether a mesh is drawn
+  pub fn begin_set_draw(&self, desc: DrawDesc) -> Result<PipelineBuild, Error> {
+    desc.validate()?;
+
+    let rebuild = desc.pipeline_changed(&self.draw);
+    Ok(PipelineBuild {
+      builder: PipelineBuilder { draw: desc, ..self.pipeline_builder() },
+      passes: rebuild.then(|| self.current_pass_build(PassTarget::Image)).flatten().into_iter().collect(),
+      change: BuildChange::Draw(desc),
+    })
+  }
+
+  // draws `data` instead of the vertex count, `None` goes back to it. the previous mesh is
+  // kept when the image pass can't be drawn with the new one
+  pub async fn set_mesh(&mut self, data: Option<&MeshData>) -> Result<(), Error> {
+    let built = self.begin_set_mesh(data)?.run().await?;
+    self.finish_build(built);
+    Ok(())
+  }
+
+  pub fn begin_set_mesh(&self, data: Option<&MeshData>) -> Result<PipelineBuild, Error> {
+    let mesh = match data {
+      Some(data) => Some(Mesh::new(&MeshCreateDesc {
+        device: &self.context.device,
+        data,
+        max_buffer_size: self.context.limits.max_buffer_size,
+      })?),
+      None => None,
+    };
+
+    let rebuild = mesh.is_some() != self.mesh.is_some();
+    Ok(PipelineBuild {
+      builder: PipelineBuilder { mesh: mesh.is_some(), ..self.pipeline_builder() },
+      passes: rebuild.then(|| self.current_pass_build(PassTarget::Image)).flatten().into_iter().collect(),
+      change: BuildChange::Mesh(mesh.map(Box::new)),
+    })
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub async fn set_mesh_from_file(&mut self, path: &std::path::Path) -> Result<(), Error> {
+    let data = std::fs::read(path).map_err(|err| Error::Mesh(err.to_string()))?;
+    self.set_mesh(Some(&MeshData::from_bytes(&data)?)).await
+  }
+
+  pub fn has_mesh(&self) -> bool {
+    self.mesh.is_some()
+  }
+
+  pub fn camera_mut(&mut self) -> &mut OrbitCamera {
+    &mut self.camera
+  }
+
+  fn update_camera_uniforms(&mut self) {
+    let model = self.mesh.as_ref().map_or(glam::Mat4::IDENTITY, |mesh| mesh.model);
+    let position = self.camera.position();
+
+    self.common_buffer_data.model = model.to_cols_array_2d();
+    self.common_buffer_data.view = self.camera.view().to_cols_array_2d();
+    self.common_buffer_data.projection = self.camera.projection(self.common_buffer_data.aspect).to_cols_array_2d();
+    self.common_buffer_data.camera_position = [position.x, position.y, position.z, if self.mesh.is_some() { 1.0 } else { 0.0 }];
+  }
+
+  pub fn set_channel(&mut self, index: usize, data: &[u8], sampler: ChannelSamplerDesc) -> Result<(), Error> {
+    if index >= MAX_CHANNELS {
+      return Err(Error::InvalidParameter(format!("channel index out of range: {}", index)));
+    }
+
+    let channel = Channel::new(&ChannelCreateDesc {
+      device: &self.context.device,
+      queue: &self.context.queue,
+      data,
+      sampler,
+      max_dimension: self.context.limits.max_texture_dimension_2d,
+    })?;
+
+    self.common_buffer_data.channel_resolution[index] = [channel.width as f32, channel.height as f32, 1.0, 0.0];
+    self.channels[index] = Some(channel);
+    self.channel_bindings.update(&self.context.device, &self.channels);
+
+    Ok(())
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn set_channel_from_file(&mut self, index: usize, path: &std::path::Path, sampler: ChannelSamplerDesc) -> Result<(), Error> {
+    let data = std::fs::read(path).map_err(image::ImageError::IoError)?;
+    self.set_channel(index, &data, sampler)
+  }
+
+  pub fn clear_channel(&mut self, index: usize) -> Result<(), Error> {
+    if index >= MAX_CHANNELS {
+      return Err(Error::InvalidParameter(format!("channel index out of range: {}", index)));
+    }
+
+    self.common_buffer_data.channel_resolution[index] = [0.0; 4];
+    self.channels[index] = None;
+    self.channel_bindings.update(&self.context.device, &self.channels);
+
+    Ok(())
+  }
+
+  // builds a throwaway pipeline for `pass`, so the result matches what `update_shader` would
+  // report without touching the shaders in use. also returns the annotated parameters, empty
+  // when the shader doesn't compile
+  pub async fn compile_shader(&self, pass: PassTarget, shader_source: &str, language: ShaderLanguage, entry_points: &EntryPoints) -> (wgpu::CompilationInfo, Vec<Parameter>) {
+    self.begin_compile_shader(pass, shader_source, language, entry_points).check().await
+  }
+
+  pub fn begin_compile_shader(&self, pass: PassTarget, shader_source: &str, language: ShaderLanguage, entry_points: &EntryPoints) -> PipelineBuild {
+    let mut build = self.begin_update_shader(pass, shader_source, language, entry_points);
+    // compiled again, the check shouldn't depend on what's cached
+    build.passes[0].shader = None;
+    build
   }
 
   pub fn destory(&mut self) {
     self.initialized = false;
   }
+
+  // xorshift32, returns a value in [0, 1)
+  fn next_seed(&mut self) -> f32 {
+    let mut x = self.rng_state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.rng_state = x;
+    (x >> 8) as f32 / (1u32 << 24) as f32
+  }
+}
+
+fn create_capture_texture(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> wgpu::Texture {
+  device.create_texture(&wgpu::TextureDescriptor {
+    label: Some("capture texture"),
+    size: wgpu::Extent3d {
+      width,
+      height,
+      depth_or_array_layers: 1,
+    },
+    mip_level_count: 1,
+    sample_count: 1,
+    dimension: wgpu::TextureDimension::D2,
+    format,
+    // also holds copies of the surface's frames
+    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+    view_formats: &[],
+  })
+}
+
+fn create_offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+  device.create_texture(&wgpu::TextureDescriptor {
+    label: Some("offscreen texture"),
+    size: wgpu::Extent3d {
+      width: config.width,
+      height: config.height,
+      depth_or_array_layers: 1,
+    },
+    mip_level_count: 1,
+    sample_count: 1,
+    dimension: wgpu::TextureDimension::D2,
+    format: config.format,
+    usage: config.usage,
+    view_formats: &[],
+  })
+}
+
+// midnight of 2000-01-01 plus `time`, so date based shaders render the same every run
+fn fixed_date(time: f32) -> [f32; 4] {
+  [2000.0, 0.0, 1.0, time.rem_euclid(86400.0)]
+}
+
+fn current_date() -> [f32; 4] {
+  cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+      // local time on the web
+      let date = js_sys::Date::new_0();
+      let seconds = date.get_hours() as f64 * 3600.0
+        + date.get_minutes() as f64 * 60.0
+        + date.get_seconds() as f64
+        + date.get_milliseconds() as f64 / 1000.0;
+      [date.get_full_year() as f32, date.get_month() as f32, date.get_date() as f32, seconds as f32]
+    } else {
+      // utc on native, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+      let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+      let days = (now.as_secs() / 86400) as i64;
+      let seconds = (now.as_secs() % 86400) as f64 + now.subsec_millis() as f64 / 1000.0;
+
+      let z = days + 719468;
+      let era = z.div_euclid(146097);
+      let doe = z - era * 146097;
+      let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+      let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+      let mp = (5 * doy + 2) / 153;
+      let day = doy - (153 * mp + 2) / 5 + 1;
+      let month = if mp < 10 { mp + 3 } else { mp - 9 };
+      let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+      [year as f32, (month - 1) as f32, day as f32, seconds as f32]
+    }
+  }
 }
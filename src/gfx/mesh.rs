@@ -0,0 +1,249 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat3, Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::error::Error;
+
+// the image pass draws a loaded mesh with a depth buffer, shaders with their own vertex
+// stage read these attributes:
+//   @location(0) position: vec3<f32>
+//   @location(1) normal: vec3<f32>
+//   @location(2) uv: vec2<f32>
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// built-in vertex stage for fragment-only shaders, needs the common uniforms prepended
+pub const MESH_WGSL: &str = include_str!("mesh.wgsl");
+
+// binary gltf starts with this magic, anything else is read as obj
+const GLB_MAGIC: &[u8] = b"glTF";
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct Vertex {
+  pub position: [f32; 3],
+  pub normal: [f32; 3],
+  pub uv: [f32; 2],
+}
+
+impl Vertex {
+  const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+
+  pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+    wgpu::VertexBufferLayout {
+      array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+      step_mode: wgpu::VertexStepMode::Vertex,
+      attributes: &Self::ATTRIBUTES,
+    }
+  }
+}
+
+// triangles of every object in the file, merged into one indexed list
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+  pub vertices: Vec<Vertex>,
+  pub indices: Vec<u32>,
+}
+
+impl MeshData {
+  // obj or binary gltf (.glb), materials and external files are ignored
+  pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+    let mesh = if data.starts_with(GLB_MAGIC) {
+      Self::from_glb(data)?
+    } else {
+      Self::from_obj(data)?
+    };
+
+    if mesh.indices.is_empty() {
+      return Err(Error::Mesh(String::from("no triangles found")));
+    }
+    Ok(mesh)
+  }
+
+  fn from_obj(data: &[u8]) -> Result<Self, Error> {
+    let options = tobj::LoadOptions {
+      single_index: true,
+      triangulate: true,
+      ignore_points: true,
+      ignore_lines: true,
+    };
+    let (models, _) = tobj::load_obj_buf(&mut std::io::Cursor::new(data), &options, |_| Err(tobj::LoadError::OpenFileFailed))
+      .map_err(|err| Error::Mesh(err.to_string()))?;
+
+    let mut mesh = Self::default();
+    for model in models.iter() {
+      let mesh_data = &model.mesh;
+      mesh.append(
+        mesh_data.positions.chunks_exact(3).map(|p| Vec3::new(p[0], p[1], p[2])).collect(),
+        mesh_data.normals.chunks_exact(3).map(|n| Vec3::new(n[0], n[1], n[2])).collect(),
+        // obj has the uv origin at the bottom left
+        mesh_data.texcoords.chunks_exact(2).map(|uv| [uv[0], 1.0 - uv[1]]).collect(),
+        &mesh_data.indices,
+      )?;
+    }
+    Ok(mesh)
+  }
+
+  fn from_glb(data: &[u8]) -> Result<Self, Error> {
+    let gltf = gltf::Gltf::from_slice(data).map_err(|err| Error::Mesh(err.to_string()))?;
+    let blob = gltf.blob.as_deref();
+
+    let mut mesh = Self::default();
+    let roots: Vec<gltf::Node> = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+      Some(scene) => scene.nodes().collect(),
+      None => gltf.nodes().collect(),
+    };
+    for node in roots {
+      mesh.append_node(&node, Mat4::IDENTITY, blob)?;
+    }
+    Ok(mesh)
+  }
+
+  fn append_node(&mut self, node: &gltf::Node, parent: Mat4, blob: Option<&[u8]>) -> Result<(), Error> {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    for primitive in node.mesh().iter().flat_map(|mesh| mesh.primitives()) {
+      if primitive.mode() != gltf::mesh::Mode::Triangles {
+        continue;
+      }
+
+      // only the binary chunk, buffers in other files can't be resolved from bytes
+      let reader = primitive.reader(|buffer| match buffer.source() {
+        gltf::buffer::Source::Bin => blob,
+        gltf::buffer::Source::Uri(_) => None,
+      });
+      let Some(positions) = reader.read_positions() else {
+        continue;
+      };
+
+      let normal_transform = Mat3::from_mat4(transform).inverse().transpose();
+      let positions: Vec<Vec3> = positions.map(|p| transform.transform_point3(Vec3::from(p))).collect();
+      let normals = reader.read_normals()
+        .map(|normals| normals.map(|n| normal_transform * Vec3::from(n)).collect())
+        .unwrap_or_default();
+      let uvs = reader.read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().collect())
+        .unwrap_or_default();
+      let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+      };
+
+      self.append(positions, normals, uvs, &indices)?;
+    }
+
+    for child in node.children() {
+      self.append_node(&child, transform, blob)?;
+    }
+    Ok(())
+  }
+
+  // normals are computed from the triangles when missing, uvs default to 0
+  fn append(&mut self, positions: Vec<Vec3>, normals: Vec<Vec3>, uvs: Vec<[f32; 2]>, indices: &[u32]) -> Result<(), Error> {
+    if let Some(index) = indices.iter().find(|index| **index as usize >= positions.len()) {
+      return Err(Error::Mesh(format!("vertex index {} out of range, there are {} vertices", index, positions.len())));
+    }
+
+    let normals = if normals.len() == positions.len() {
+      normals
+    } else {
+      smooth_normals(&positions, indices)
+    };
+
+    let offset = self.vertices.len() as u32;
+    self.vertices.extend(positions.iter().enumerate().map(|(i, position)| Vertex {
+      position: position.to_array(),
+      normal: normals[i].normalize_or_zero().to_array(),
+      uv: uvs.get(i).copied().unwrap_or_default(),
+    }));
+    self.indices.extend(indices.chunks_exact(3).flatten().map(|index| index + offset));
+    Ok(())
+  }
+
+  // center and radius of the bounding box
+  pub fn bounds(&self) -> (Vec3, f32) {
+    let (min, max) = self.vertices.iter()
+      .map(|vertex| Vec3::from(vertex.position))
+      .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), position| (min.min(position), max.max(position)));
+    ((min + max) * 0.5, (max - min).length() * 0.5)
+  }
+}
+
+// area weighted average of the faces around each vertex
+fn smooth_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+  let mut normals = vec![Vec3::ZERO; positions.len()];
+  for triangle in indices.chunks_exact(3) {
+    let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+    let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+    for index in [a, b, c] {
+      normals[index] += normal;
+    }
+  }
+  normals
+}
+
+#[derive(Debug)]
+pub struct Mesh {
+  pub vertex_buffer: wgpu::Buffer,
+  pub index_buffer: wgpu::Buffer,
+  pub index_count: u32,
+  // centers the mesh and scales it to fit the unit sphere
+  pub model: Mat4,
+}
+
+#[derive(Debug)]
+pub struct MeshCreateDesc<'a> {
+  pub device: &'a wgpu::Device,
+  pub data: &'a MeshData,
+  pub max_buffer_size: u64,
+}
+
+impl Mesh {
+  pub fn new(create_desc: &MeshCreateDesc) -> Result<Self, Error> {
+    let device = create_desc.device;
+    let data = create_desc.data;
+
+    let vertex_bytes = std::mem::size_of_val(data.vertices.as_slice()) as u64;
+    if vertex_bytes > create_desc.max_buffer_size || data.indices.len() as u64 * 4 > create_desc.max_buffer_size {
+      return Err(Error::Mesh(format!("{} vertices and {} indices don't fit the device's buffers", data.vertices.len(), data.indices.len())));
+    }
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("mesh vertex buffer"),
+      contents: bytemuck::cast_slice(&data.vertices),
+      usage: wgpu::BufferUsages::VERTEX,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("mesh index buffer"),
+      contents: bytemuck::cast_slice(&data.indices),
+      usage: wgpu::BufferUsages::INDEX,
+    });
+
+    let (center, radius) = data.bounds();
+    let scale = if radius > 0.0 { 1.0 / radius } else { 1.0 };
+
+    Ok(Self {
+      vertex_buffer,
+      index_buffer,
+      index_count: data.indices.len() as u32,
+      model: Mat4::from_scale(Vec3::splat(scale)) * Mat4::from_translation(-center),
+    })
+  }
+}
+
+pub fn create_depth_view(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+  let texture = device.create_texture(&wgpu::TextureDescriptor {
+    label: Some("depth texture"),
+    size: wgpu::Extent3d {
+      width,
+      height,
+      depth_or_array_layers: 1,
+    },
+    mip_level_count: 1,
+    sample_count: 1,
+    dimension: wgpu::TextureDimension::D2,
+    format: DEPTH_FORMAT,
+    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    view_formats: &[],
+  });
+  texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
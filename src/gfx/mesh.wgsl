@@ -0,0 +1,23 @@
+// built-in vertex stage for meshes, the output matches `MeshInput`
+struct MeshOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+  @location(1) normal: vec3<f32>,
+  @location(2) world_position: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+  @location(0) position: vec3<f32>,
+  @location(1) normal: vec3<f32>,
+  @location(2) uv: vec2<f32>,
+) -> MeshOutput {
+  let world_position = common_uniforms.model * vec4<f32>(position, 1.0);
+  var output: MeshOutput;
+  output.position = common_uniforms.projection * common_uniforms.view * world_position;
+  output.uv = uv;
+  // the model matrix scales uniformly, no inverse transpose needed
+  output.normal = normalize((common_uniforms.model * vec4<f32>(normal, 0.0)).xyz);
+  output.world_position = world_position.xyz;
+  return output;
+}
@@ -1,4 +1,16 @@
-
+pub mod buffer_pass;
+pub mod camera;
+pub mod capture;
+pub mod channels;
+pub mod context;
+pub mod export;
 pub mod gfx_state;
+pub mod mesh;
+pub mod parameters;
 pub mod pipeline;
+pub mod pipeline_build;
+pub mod playback;
+pub mod shader;
+pub mod storage;
 pub mod uniform_buffer;
+pub mod user_uniforms;
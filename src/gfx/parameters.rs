@@ -0,0 +1,636 @@
+use std::collections::HashMap;
+
+use super::user_uniforms::{UniformField, UniformLayout};
+
+// tweakable values are user uniform fields and wgsl `override` constants annotated in a
+// line comment on the declaration or in comment lines right above it, e.g.
+//   struct Params {
+//     speed: f32, // @range(0, 10, 0.5) @default(2.5) @label("Speed")
+//     // @color @default(1, 0.5, 0)
+//     tint: vec3<f32>,
+//   };
+//   override glow = 1u; // @toggle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+  Uniform,
+  Override,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Widget {
+  Slider,
+  Color,
+  Toggle,
+  Input,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+  // the uniform field name as taken by `set_uniform`, or the override name as taken by
+  // `set_override`
+  pub name: String,
+  pub kind: ParameterKind,
+  pub type_name: String,
+  pub widget: Widget,
+  pub label: Option<String>,
+  pub min: Option<f64>,
+  pub max: Option<f64>,
+  pub step: Option<f64>,
+  // one value per component, matrices are column major
+  pub default: Option<Vec<f64>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Annotations {
+  range: Option<(f64, f64, Option<f64>)>,
+  default: Option<Vec<f64>>,
+  color: bool,
+  toggle: bool,
+  label: Option<String>,
+  dispatch: Option<[u32; 3]>,
+}
+
+impl Annotations {
+  fn is_empty(&self) -> bool {
+    self.range.is_none() && self.default.is_none() && !self.color && !self.toggle && self.label.is_none() && self.dispatch.is_none()
+  }
+
+  // later annotations win
+  fn merge(&mut self, other: Annotations) {
+    self.range = other.range.or(self.range);
+    self.default = other.default.or(self.default.take());
+    self.color |= other.color;
+    self.toggle |= other.toggle;
+    self.label = other.label.or(self.label.take());
+    self.dispatch = other.dispatch.or(self.dispatch);
+  }
+}
+
+// the parameters declared by `source`, `module` and `uniforms` are its reflection
+pub fn reflect(source: &str, module: &naga::Module, uniforms: Option<&UniformLayout>) -> Vec<Parameter> {
+  let annotations = scan(source);
+  if annotations.is_empty() {
+    return Vec::new();
+  }
+
+  let mut parameters = Vec::new();
+
+  for field in uniforms.map(|layout| layout.fields.as_slice()).unwrap_or_default() {
+    if let Some(annotations) = annotations.get(&field.declaration) {
+      parameters.push(uniform_parameter(field, annotations));
+    }
+  }
+
+  for (_, constant) in module.overrides.iter() {
+    let Some(name) = constant.name.as_ref() else {
+      continue;
+    };
+    if let Some(annotations) = annotations.get(&(None, name.clone())) {
+      parameters.push(override_parameter(module, constant, name, annotations));
+    }
+  }
+
+  parameters
+}
+
+fn uniform_parameter(field: &UniformField, annotations: &Annotations) -> Parameter {
+  let is_color = field.columns == 1 && (field.rows == 3 || field.rows == 4);
+  if annotations.color && !is_color {
+    log::warn!("[gfx] @color on `{}` needs a vec3 or vec4, got {}", field.name, field.type_name());
+  }
+
+  let widget = widget(annotations, annotations.color && is_color, field.len() == 1);
+  parameter(field.name.clone(), ParameterKind::Uniform, field.type_name(), widget, field.len(), annotations, None)
+}
+
+fn override_parameter(module: &naga::Module, constant: &naga::Override, name: &str, annotations: &Annotations) -> Parameter {
+  let type_name = match &module.types[constant.ty].inner {
+    naga::TypeInner::Scalar(scalar) => match (scalar.kind, scalar.width) {
+      (naga::ScalarKind::Bool, _) => "bool",
+      (naga::ScalarKind::Float, 2) => "f16",
+      (naga::ScalarKind::Float, 8) => "f64",
+      (naga::ScalarKind::Float, _) => "f32",
+      (naga::ScalarKind::Sint, _) => "i32",
+      _ => "u32",
+    },
+    _ => "unknown",
+  };
+
+  // the initializer is only known when it's a literal
+  let initializer = constant.init.and_then(|init| match module.global_expressions[init] {
+    naga::Expression::Literal(literal) => literal_value(literal),
+    _ => None,
+  });
+
+  if annotations.color {
+    log::warn!("[gfx] @color on `{}` needs a vec3 or vec4, overrides are scalars", name);
+  }
+
+  let widget = widget(annotations, false, true);
+  parameter(name.to_string(), ParameterKind::Override, type_name.to_string(), widget, 1, annotations, initializer)
+}
+
+fn widget(annotations: &Annotations, color: bool, scalar: bool) -> Widget {
+  if color {
+    Widget::Color
+  } else if annotations.toggle && scalar {
+    Widget::Toggle
+  } else if annotations.range.is_some() && scalar {
+    Widget::Slider
+  } else {
+    Widget::Input
+  }
+}
+
+fn parameter(
+  name: String,
+  kind: ParameterKind,
+  type_name: String,
+  widget: Widget,
+  len: usize,
+  annotations: &Annotations,
+  initializer: Option<f64>,
+) -> Parameter {
+  let default = match annotations.default.as_ref() {
+    Some(values) if values.len() == len => Some(values.clone()),
+    // `@default(0.5)` on a vector or colour fills every component
+    Some(values) if values.len() == 1 => Some(vec![values[0]; len]),
+    Some(values) => {
+      log::warn!("[gfx] @default of `{}` has {} values, {} takes {}", name, values.len(), type_name, len);
+      None
+    },
+    None => initializer.map(|value| vec![value]),
+  };
+
+  let (min, max, step) = match annotations.range {
+    Some((min, max, step)) => (Some(min), Some(max), step),
+    None => (None, None, None),
+  };
+
+  Parameter {
+    name,
+    kind,
+    type_name,
+    widget,
+    label: annotations.label.clone(),
+    min,
+    max,
+    step,
+    default,
+  }
+}
+
+fn literal_value(literal: naga::Literal) -> Option<f64> {
+  match literal {
+    naga::Literal::F64(value) | naga::Literal::AbstractFloat(value) => Some(value),
+    naga::Literal::F32(value) => Some(value as f64),
+    naga::Literal::U32(value) => Some(value as f64),
+    naga::Literal::I32(value) => Some(value as f64),
+    naga::Literal::U64(value) => Some(value as f64),
+    naga::Literal::I64(value) | naga::Literal::AbstractInt(value) => Some(value as f64),
+    naga::Literal::Bool(value) => Some(if value { 1.0 } else { 0.0 }),
+  }
+}
+
+// annotations by declaration, keyed like `UniformField::declaration`
+fn scan(source: &str) -> HashMap<(Option<String>, String), Annotations> {
+  let mut declarations = HashMap::new();
+  let mut current_struct: Option<String> = None;
+  // collected from comment-only lines until the next declaration
+  let mut pending = Annotations::default();
+
+  for line in source.lines() {
+    let (code, comment) = match line.find("//") {
+      Some(index) => (line[..index].trim(), Some(&line[index + 2..])),
+      None => (line.trim(), None),
+    };
+
+    let mut annotations = std::mem::take(&mut pending);
+    if let Some(comment) = comment {
+      annotations.merge(parse_annotations(comment));
+    }
+
+    if code.is_empty() {
+      if comment.is_some() {
+        pending = annotations;
+      }
+      continue;
+    }
+
+    if let Some(name) = struct_name(code) {
+      current_struct = Some(name);
+      continue;
+    }
+
+    if let Some(name) = declaration_name(code) {
+      if !annotations.is_empty() {
+        let key = match is_global(code) {
+          true => (None, name),
+          false => (current_struct.clone(), name),
+        };
+        declarations.entry(key).or_insert_with(Annotations::default).merge(annotations);
+      }
+    }
+
+    if code.contains('}') {
+      current_struct = None;
+    }
+  }
+
+  declarations
+}
+
+// invocations of compute entry points annotated with `@dispatch(x, y, z)` by function name,
+// in comment lines above the function or its attributes, e.g.
+//   // @dispatch(1024)
+//   @compute @workgroup_size(64)
+//   fn update(@builtin(global_invocation_id) id: vec3<u32>) {
+pub fn dispatch_sizes(source: &str) -> HashMap<String, [u32; 3]> {
+  let mut sizes = HashMap::new();
+  let mut pending: Option<[u32; 3]> = None;
+
+  for line in source.lines() {
+    let (code, comment) = match line.find("//") {
+      Some(index) => (line[..index].trim(), Some(&line[index + 2..])),
+      None => (line.trim(), None),
+    };
+
+    if let Some(dispatch) = comment.and_then(|comment| parse_annotations(comment).dispatch) {
+      pending = Some(dispatch);
+    }
+
+    let code = strip_attributes(code);
+    if let Some(rest) = code.strip_prefix("fn ") {
+      let name = rest.split('(').next().unwrap_or_default().trim();
+      if let Some(dispatch) = pending.take() {
+        sizes.insert(name.to_string(), dispatch);
+      }
+    } else if !code.is_empty() {
+      pending = None;
+    }
+  }
+
+  sizes
+}
+
+// `struct Name {` in either language or a glsl `uniform Name {` block
+fn struct_name(code: &str) -> Option<String> {
+  let code = strip_attributes(code);
+  let mut words = code.split(|c: char| c.is_whitespace() || c == '{').filter(|word| !word.is_empty());
+  match words.next()? {
+    "struct" => words.next().map(String::from),
+    "uniform" if code.contains('{') || code.split_whitespace().count() == 2 => words.next().filter(|name| is_identifier(name)).map(String::from),
+    _ => None,
+  }
+}
+
+fn is_global(code: &str) -> bool {
+  let code = strip_attributes(code);
+  code.starts_with("override") || code.starts_with("var")
+}
+
+// `name` of `name: type`, `override name` and glsl `type name;`
+fn declaration_name(code: &str) -> Option<String> {
+  let code = strip_attributes(code);
+
+  let name = if let Some(rest) = code.strip_prefix("override") {
+    rest.split([':', '=', ';']).next()?.trim()
+  } else if let Some((before, _)) = code.split_once(':') {
+    before.rsplit(|c: char| c.is_whitespace() || c == '>').next()?
+  } else {
+    let code = code.split([';', '[', '=']).next()?;
+    code.split_whitespace().last()?
+  };
+
+  is_identifier(name).then(|| name.to_string())
+}
+
+// drops leading `@group(3)`, `@id(0)` and `layout(...)` attributes
+fn strip_attributes(code: &str) -> &str {
+  let mut code = code.trim_start();
+  loop {
+    let rest = if let Some(rest) = code.strip_prefix('@') {
+      rest.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_')
+    } else if let Some(rest) = code.strip_prefix("layout") {
+      rest
+    } else {
+      return code;
+    };
+
+    let rest = rest.trim_start();
+    code = match rest.strip_prefix('(') {
+      Some(args) => args.split_once(')').map(|(_, rest)| rest).unwrap_or_default().trim_start(),
+      None => rest,
+    };
+  }
+}
+
+fn is_identifier(name: &str) -> bool {
+  name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+    && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// unknown `@words` are treated as prose, malformed known ones are logged and skipped
+fn parse_annotations(comment: &str) -> Annotations {
+  let mut annotations = Annotations::default();
+  let mut rest = comment;
+
+  while let Some(index) = rest.find('@') {
+    rest = &rest[index + 1..];
+    let name_len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+    let name = &rest[..name_len];
+    rest = &rest[name_len..];
+
+    let args = match rest.trim_start().strip_prefix('(') {
+      Some(after) => match split_args(after) {
+        Some((args, after)) => {
+          rest = after;
+          args
+        },
+        None => {
+          log::warn!("[gfx] unclosed @{}(", name);
+          break;
+        },
+      },
+      None => Vec::new(),
+    };
+
+    let numbers = || args.iter().map(|arg| parse_number(arg)).collect::<Option<Vec<_>>>();
+
+    match name {
+      "range" => match numbers().as_deref() {
+        Some([min, max]) => annotations.range = Some((*min, *max, None)),
+        Some([min, max, step]) => annotations.range = Some((*min, *max, Some(*step))),
+        _ => log::warn!("[gfx] @range takes a min, a max and an optional step"),
+      },
+      "default" => match numbers() {
+        Some(values) if !values.is_empty() => annotations.default = Some(values),
+        _ => log::warn!("[gfx] @default takes one or more numbers"),
+      },
+      "color" => annotations.color = true,
+      "toggle" => annotations.toggle = true,
+      "label" => match args.as_slice() {
+        [label] => annotations.label = Some(unquote(label)),
+        _ => log::warn!("[gfx] @label takes a single string"),
+      },
+      "dispatch" => match numbers().as_deref() {
+        Some(counts) if (1..=3).contains(&counts.len()) && counts.iter().all(|count| *count >= 1.0 && count.fract() == 0.0) => {
+          annotations.dispatch = Some(std::array::from_fn(|i| counts.get(i).map(|count| *count as u32).unwrap_or(1)));
+        },
+        _ => log::warn!("[gfx] @dispatch takes one to three positive integers"),
+      },
+      _ => {},
+    }
+  }
+
+  annotations
+}
+
+// the comma separated arguments up to the closing parenthesis and what follows it,
+// commas and parentheses inside quotes don't count
+fn split_args(text: &str) -> Option<(Vec<String>, &str)> {
+  let mut args = Vec::new();
+  let mut current = String::new();
+  let mut quoted = false;
+  let mut escaped = false;
+
+  for (index, c) in text.char_indices() {
+    match c {
+      _ if escaped => {
+        escaped = false;
+        current.push(c);
+      },
+      '\\' if quoted => {
+        escaped = true;
+        current.push(c);
+      },
+      '"' => {
+        quoted = !quoted;
+        current.push(c);
+      },
+      ',' if !quoted => args.push(std::mem::take(&mut current).trim().to_string()),
+      ')' if !quoted => {
+        if !current.trim().is_empty() || !args.is_empty() {
+          args.push(current.trim().to_string());
+        }
+        return Some((args, &text[index + 1..]));
+      },
+      _ => current.push(c),
+    }
+  }
+
+  None
+}
+
+// `"a \"b\""` to `a "b"`, unquoted labels are taken as they are
+fn unquote(arg: &str) -> String {
+  match arg.strip_prefix('"').and_then(|arg| arg.strip_suffix('"')) {
+    Some(inner) => inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+    None => arg.to_string(),
+  }
+}
+
+fn parse_number(arg: &str) -> Option<f64> {
+  match arg {
+    "true" => Some(1.0),
+    "false" => Some(0.0),
+    _ => arg.trim_end_matches(['f', 'u', 'i']).parse::<f64>().ok().filter(|value| value.is_finite()),
+  }
+}
+
+// the schema handed to editors, `{ "parameters": [...] }` with one entry per parameter
+pub fn schema_json(parameters: &[Parameter]) -> String {
+  let entries: Vec<String> = parameters.iter().map(parameter_json).collect();
+  format!("{{\"parameters\":[{}]}}", entries.join(","))
+}
+
+fn parameter_json(parameter: &Parameter) -> String {
+  let kind = match parameter.kind {
+    ParameterKind::Uniform => "uniform",
+    ParameterKind::Override => "override",
+  };
+  let widget = match parameter.widget {
+    Widget::Slider => "slider",
+    Widget::Color => "color",
+    Widget::Toggle => "toggle",
+    Widget::Input => "input",
+  };
+
+  let mut entries = vec![
+    format!("\"name\":{}", json_string(&parameter.name)),
+    format!("\"kind\":\"{}\"", kind),
+    format!("\"type\":{}", json_string(&parameter.type_name)),
+    format!("\"widget\":\"{}\"", widget),
+  ];
+  if let Some(label) = parameter.label.as_ref() {
+    entries.push(format!("\"label\":{}", json_string(label)));
+  }
+  for (key, value) in [("min", parameter.min), ("max", parameter.max), ("step", parameter.step)] {
+    if let Some(value) = value {
+      entries.push(format!("\"{}\":{}", key, value));
+    }
+  }
+  // scalars get a plain number, like `set_uniform` takes them
+  match parameter.default.as_deref() {
+    Some([value]) => entries.push(format!("\"default\":{}", value)),
+    Some(values) => {
+      let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+      entries.push(format!("\"default\":[{}]", values.join(",")));
+    },
+    None => {},
+  }
+
+  format!("{{{}}}", entries.join(","))
+}
+
+fn json_string(value: &str) -> String {
+  let mut json = String::from("\"");
+  for c in value.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+      c => json.push(c),
+    }
+  }
+  json.push('"');
+  json
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_annotations() {
+    let annotations = parse_annotations(r#" speed @range(0, 10, 0.5) @default(2.5) @label("Speed, in \"m/s\"") @color"#);
+    assert_eq!(annotations.range, Some((0.0, 10.0, Some(0.5))));
+    assert_eq!(annotations.default, Some(vec![2.5]));
+    assert_eq!(annotations.label.as_deref(), Some(r#"Speed, in "m/s""#));
+    assert!(annotations.color);
+    assert!(!annotations.toggle);
+
+    let annotations = parse_annotations(" @toggle @default(true) @dispatch(64, 2)");
+    assert!(annotations.toggle);
+    assert_eq!(annotations.default, Some(vec![1.0]));
+    assert_eq!(annotations.dispatch, Some([64, 2, 1]));
+  }
+
+  #[test]
+  fn skips_unknown_and_malformed_annotations() {
+    assert!(parse_annotations(" mail me @ home, see @todo(later)").is_empty());
+    assert!(parse_annotations(" @range(1) @default() @dispatch(0) @label(a, b)").is_empty());
+    assert!(parse_annotations(" @range(0, 1").is_empty());
+    assert_eq!(parse_annotations(" @default(1f, 2u, -3i)").default, Some(vec![1.0, 2.0, -3.0]));
+  }
+
+  #[test]
+  fn extracts_declaration_names() {
+    assert_eq!(declaration_name("speed: f32,").as_deref(), Some("speed"));
+    assert_eq!(declaration_name("@align(16) tint: vec3<f32>,").as_deref(), Some("tint"));
+    assert_eq!(declaration_name("@group(3) @binding(0) var<uniform> params: Params;").as_deref(), Some("params"));
+    assert_eq!(declaration_name("override glow = 1u;").as_deref(), Some("glow"));
+    assert_eq!(declaration_name("@id(0) override count: u32 = 4;").as_deref(), Some("count"));
+    assert_eq!(declaration_name("float speed;").as_deref(), Some("speed"));
+    assert_eq!(declaration_name("vec3 tints[2];").as_deref(), Some("tints"));
+    assert_eq!(declaration_name("layout(offset = 0) float speed = 1.0;").as_deref(), Some("speed"));
+    assert_eq!(declaration_name("return 1.0;"), None);
+  }
+
+  #[test]
+  fn finds_struct_names() {
+    assert_eq!(struct_name("struct Params {").as_deref(), Some("Params"));
+    assert_eq!(struct_name("layout(std140) uniform Params {").as_deref(), Some("Params"));
+    assert_eq!(struct_name("uniform float speed;"), None);
+    assert_eq!(struct_name("speed: f32,"), None);
+  }
+
+  #[test]
+  fn scans_annotations_by_declaration() {
+    let declarations = scan(concat!(
+      "struct Params {\n",
+      "  speed: f32, // @range(0, 10)\n",
+      "  // @color\n",
+      "  // @default(1, 0.5, 0)\n",
+      "  tint: vec3<f32>,\n",
+      "  plain: f32,\n",
+      "};\n",
+      "override glow = 1u; // @toggle\n",
+    ));
+
+    let params = |name: &str| (Some(String::from("Params")), String::from(name));
+    assert_eq!(declarations.len(), 3);
+    assert_eq!(declarations[&params("speed")].range, Some((0.0, 10.0, None)));
+    assert!(declarations[&params("tint")].color);
+    assert_eq!(declarations[&params("tint")].default, Some(vec![1.0, 0.5, 0.0]));
+    assert!(declarations[&(None, String::from("glow"))].toggle);
+  }
+
+  #[test]
+  fn reflects_overrides() {
+    let source = concat!(
+      "override speed = 2.0; // @range(0, 10) @label(\"Speed\")\n",
+      "// @toggle\n",
+      "@id(1) override glow: bool = true;\n",
+      "override plain: f32 = 1.0;\n",
+      "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(speed * f32(glow) * plain); }\n",
+    );
+    let module = naga::front::wgsl::parse_str(source).unwrap();
+
+    let parameters = reflect(source, &module, None);
+    assert_eq!(parameters.len(), 2);
+    assert_eq!(parameters[0].name, "speed");
+    assert_eq!(parameters[0].kind, ParameterKind::Override);
+    assert_eq!(parameters[0].widget, Widget::Slider);
+    assert_eq!(parameters[0].label.as_deref(), Some("Speed"));
+    // the initializer when there is no `@default`
+    assert_eq!(parameters[0].default, Some(vec![2.0]));
+    assert_eq!(parameters[1].name, "glow");
+    assert_eq!(parameters[1].type_name, "bool");
+    assert_eq!(parameters[1].widget, Widget::Toggle);
+  }
+
+  #[test]
+  fn escapes_json_strings() {
+    assert_eq!(json_string("plain"), r#""plain""#);
+    assert_eq!(json_string(r#"say "hi" \ bye"#), r#""say \"hi\" \\ bye""#);
+    assert_eq!(json_string("line\nbreak\ttab"), r#""line\u000abreak\u0009tab""#);
+    assert_eq!(json_string("größe"), "\"größe\"");
+  }
+
+  #[test]
+  fn writes_the_schema() {
+    let parameters = [
+      Parameter {
+        name: String::from("speed"),
+        kind: ParameterKind::Uniform,
+        type_name: String::from("f32"),
+        widget: Widget::Slider,
+        label: Some(String::from("\"Speed\"")),
+        min: Some(0.0),
+        max: Some(10.0),
+        step: None,
+        default: Some(vec![2.5]),
+      },
+      Parameter {
+        name: String::from("tint"),
+        kind: ParameterKind::Uniform,
+        type_name: String::from("vec3<f32>"),
+        widget: Widget::Color,
+        label: None,
+        min: None,
+        max: None,
+        step: None,
+        default: Some(vec![1.0, 0.5, 0.0]),
+      },
+    ];
+
+    assert_eq!(schema_json(&parameters), concat!(
+      r#"{"parameters":["#,
+      r#"{"name":"speed","kind":"uniform","type":"f32","widget":"slider","label":"\"Speed\"","min":0,"max":10,"default":2.5},"#,
+      r#"{"name":"tint","kind":"uniform","type":"vec3<f32>","widget":"color","default":[1,0.5,0]}"#,
+      r#"]}"#,
+    ));
+    assert_eq!(schema_json(&[]), r#"{"parameters":[]}"#);
+  }
+}
@@ -1,72 +1,249 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use super::mesh::{Vertex, DEPTH_FORMAT};
+
+// vertex and instance counts above this are most likely typos that would stall the gpu
+pub const MAX_DRAW_VERTICES: u64 = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+  #[default]
+  Replace,
+  Alpha,
+  PremultipliedAlpha,
+  Additive,
+}
+
+impl BlendMode {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "replace" => Some(BlendMode::Replace),
+      "alpha" => Some(BlendMode::Alpha),
+      "premultiplied" => Some(BlendMode::PremultipliedAlpha),
+      "additive" => Some(BlendMode::Additive),
+      _ => None,
+    }
+  }
+
+  fn blend_state(self) -> wgpu::BlendState {
+    match self {
+      BlendMode::Replace => wgpu::BlendState::REPLACE,
+      BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+      BlendMode::PremultipliedAlpha => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+      BlendMode::Additive => wgpu::BlendState {
+        color: wgpu::BlendComponent {
+          src_factor: wgpu::BlendFactor::SrcAlpha,
+          dst_factor: wgpu::BlendFactor::One,
+          operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent {
+          src_factor: wgpu::BlendFactor::One,
+          dst_factor: wgpu::BlendFactor::One,
+          operation: wgpu::BlendOperation::Add,
+        },
+      },
+    }
+  }
+}
+
+// how the image pass is drawn, the default draws the fullscreen triangle. with more
+// vertices a shader's own vertex stage builds geometry from `vertex_index` and
+// `instance_index`, vertexshaderart style
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawDesc {
+  pub vertex_count: u32,
+  pub instance_count: u32,
+  pub topology: wgpu::PrimitiveTopology,
+  pub cull_mode: Option<wgpu::Face>,
+  pub blend: BlendMode,
+  // linear rgba the target starts from every frame
+  pub clear_color: wgpu::Color,
+}
+
+impl Default for DrawDesc {
+  fn default() -> Self {
+    Self {
+      vertex_count: 3,
+      instance_count: 1,
+      topology: wgpu::PrimitiveTopology::TriangleList,
+      cull_mode: Some(wgpu::Face::Back),
+      blend: BlendMode::Replace,
+      clear_color: wgpu::Color::RED,
+    }
+  }
+}
+
+impl DrawDesc {
+  pub fn validate(&self) -> Result<(), Error> {
+    let vertices = self.vertex_count as u64 * self.instance_count as u64;
+    if self.vertex_count == 0 || self.instance_count == 0 || vertices > MAX_DRAW_VERTICES {
+      return Err(Error::InvalidParameter(format!(
+        "vertex and instance count must be positive with at most {} vertices in total, got {} x {}",
+        MAX_DRAW_VERTICES, self.vertex_count, self.instance_count,
+      )));
+    }
+    Ok(())
+  }
+
+  // settings baked into the pipeline, the counts and clear color are not
+  pub fn pipeline_changed(&self, other: &DrawDesc) -> bool {
+    self.topology != other.topology || self.cull_mode != other.cull_mode || self.blend != other.blend
+  }
+}
+
+pub fn topology_from_name(name: &str) -> Option<wgpu::PrimitiveTopology> {
+  match name {
+    "points" => Some(wgpu::PrimitiveTopology::PointList),
+    "lines" => Some(wgpu::PrimitiveTopology::LineList),
+    "line-strip" => Some(wgpu::PrimitiveTopology::LineStrip),
+    "triangles" => Some(wgpu::PrimitiveTopology::TriangleList),
+    "triangle-strip" => Some(wgpu::PrimitiveTopology::TriangleStrip),
+    _ => None,
+  }
+}
+
+// `None` for "none"
+pub fn cull_mode_from_name(name: &str) -> Option<Option<wgpu::Face>> {
+  match name {
+    "none" => Some(None),
+    "front" => Some(Some(wgpu::Face::Front)),
+    "back" => Some(Some(wgpu::Face::Back)),
+    _ => None,
+  }
+}
 
 #[derive(Debug)]
 pub struct Pipeline {
   pub pipeline: wgpu::RenderPipeline,
+  // dispatched in order before the image pass of every frame
+  pub compute: Vec<ComputePipeline>,
+}
+
+#[derive(Debug)]
+pub struct ComputePipeline {
+  pub pipeline: wgpu::ComputePipeline,
+  pub workgroup_size: [u32; 3],
+  // total invocations, the render size when `None`
+  pub invocations: Option<[u32; 3]>,
+}
+
+#[derive(Debug)]
+pub struct ShaderStage<'a> {
+  pub module: &'a wgpu::ShaderModule,
+  pub entry_point: &'a str,
+  // values of the module's `override` constants, by name or `@id`
+  pub constants: &'a HashMap<String, f64>,
+}
+
+impl ShaderStage<'_> {
+  fn compilation_options(&self) -> wgpu::PipelineCompilationOptions<'_> {
+    wgpu::PipelineCompilationOptions {
+      constants: self.constants,
+      ..Default::default()
+    }
+  }
 }
 
 #[derive(Debug)]
 pub struct PipelineCreateDesc<'a> {
   pub device: &'a wgpu::Device,
-  pub config: &'a wgpu::SurfaceConfiguration,
-  pub shader_source: &'a str,
-  pub bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
+  pub layout: &'a wgpu::PipelineLayout,
+  pub format: wgpu::TextureFormat,
+  pub vertex: ShaderStage<'a>,
+  pub fragment: ShaderStage<'a>,
+  pub draw: &'a DrawDesc,
+  // draws the mesh's vertex and index buffers with depth testing
+  pub mesh: bool,
+  pub cache: Option<&'a wgpu::PipelineCache>,
 }
 
 impl Pipeline {
   pub fn new(create_desc: &PipelineCreateDesc) -> Self {
     let device = create_desc.device;
-    let config = create_desc.config;
-    let shader_source = create_desc.shader_source;
 
-    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-      label: Some("Shader"),
-      source: wgpu::ShaderSource::Wgsl(shader_source.into()),
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-      label: Some("Pipeline Layout"),
-      bind_group_layouts: create_desc.bind_group_layouts,
-      push_constant_ranges: &[],
-    });
+    let vertex_buffers = if create_desc.mesh { vec![Vertex::layout()] } else { Vec::new() };
+    let strip = matches!(create_desc.draw.topology, wgpu::PrimitiveTopology::LineStrip | wgpu::PrimitiveTopology::TriangleStrip);
 
     let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
       label: Some("Render Pipeline"),
-      layout: Some(&pipeline_layout),
+      layout: Some(create_desc.layout),
       vertex: wgpu::VertexState {
-        module: &shader,
-        entry_point: "vs_main",
-        buffers: &[],
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
+        module: create_desc.vertex.module,
+        entry_point: create_desc.vertex.entry_point,
+        buffers: &vertex_buffers,
+        compilation_options: create_desc.vertex.compilation_options(),
       },
       fragment: Some(wgpu::FragmentState {
-        module: &shader,
-        entry_point: "fs_main",
+        module: create_desc.fragment.module,
+        entry_point: create_desc.fragment.entry_point,
         targets: &[Some(wgpu::ColorTargetState {
-          format: config.format,
-          blend: Some(wgpu::BlendState::REPLACE),
+          format: create_desc.format,
+          blend: Some(create_desc.draw.blend.blend_state()),
           write_mask: wgpu::ColorWrites::ALL,
         })],
-        compilation_options: wgpu::PipelineCompilationOptions::default(),
+        compilation_options: create_desc.fragment.compilation_options(),
       }),
       primitive: wgpu::PrimitiveState {
-        topology: wgpu::PrimitiveTopology::TriangleList,
-        strip_index_format: None,
+        topology: create_desc.draw.topology,
+        strip_index_format: (create_desc.mesh && strip).then_some(wgpu::IndexFormat::Uint32),
         front_face: wgpu::FrontFace::Ccw,
-        cull_mode: Some(wgpu::Face::Back),
+        cull_mode: create_desc.draw.cull_mode,
         polygon_mode: wgpu::PolygonMode::Fill,
         unclipped_depth: false,
         conservative: false,
       },
-      depth_stencil: None,
+      depth_stencil: create_desc.mesh.then(|| wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+      }),
       multisample: wgpu::MultisampleState {
         count: 1,
         mask: !0,
         alpha_to_coverage_enabled: false,
       },
       multiview: None,
-      cache: None,
+      cache: create_desc.cache,
+    });
+
+    Self { pipeline, compute: Vec::new() }
+  }
+}
+
+#[derive(Debug)]
+pub struct ComputePipelineCreateDesc<'a> {
+  pub device: &'a wgpu::Device,
+  pub layout: &'a wgpu::PipelineLayout,
+  pub compute: ShaderStage<'a>,
+  pub workgroup_size: [u32; 3],
+  pub invocations: Option<[u32; 3]>,
+  pub cache: Option<&'a wgpu::PipelineCache>,
+}
+
+impl ComputePipeline {
+  pub fn new(create_desc: &ComputePipelineCreateDesc) -> Self {
+    let pipeline = create_desc.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+      label: Some("Compute Pipeline"),
+      layout: Some(create_desc.layout),
+      module: create_desc.compute.module,
+      entry_point: create_desc.compute.entry_point,
+      compilation_options: create_desc.compute.compilation_options(),
+      cache: create_desc.cache,
     });
 
-    Self { pipeline }
+    Self {
+      pipeline,
+      workgroup_size: create_desc.workgroup_size,
+      invocations: create_desc.invocations,
+    }
+  }
+
+  // enough workgroups to cover every invocation
+  pub fn workgroups(&self, width: u32, height: u32) -> [u32; 3] {
+    let invocations = self.invocations.unwrap_or([width, height, 1]);
+    std::array::from_fn(|i| invocations[i].div_ceil(self.workgroup_size[i].max(1)))
   }
 }
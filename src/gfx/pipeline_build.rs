@@ -0,0 +1,276 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::error::Error;
+use super::{
+  buffer_pass::PassTarget,
+  context::GfxContext,
+  mesh::Mesh,
+  parameters::{self, Parameter},
+  pipeline::{ComputePipeline, ComputePipelineCreateDesc, DrawDesc, Pipeline, PipelineCreateDesc, ShaderStage},
+  shader::{self, CompiledShader, EntryPoints, ShaderLanguage, WgslShader},
+  storage::StorageUsage,
+  user_uniforms::UniformLayout,
+};
+
+// what pipelines are built with, cloned out of `GfxState` so a build can be awaited without
+// borrowing it
+#[derive(Debug, Clone)]
+pub struct PipelineBuilder {
+  pub context: Arc<GfxContext>,
+  pub layout: Arc<wgpu::PipelineLayout>,
+  // `None` when the device can't run compute passes
+  pub compute_layout: Option<Arc<wgpu::PipelineLayout>>,
+  // group of the storage buffers and textures
+  pub storage_group: u32,
+  pub output_format: wgpu::TextureFormat,
+  pub buffer_format: wgpu::TextureFormat,
+  // how the image pass is drawn
+  pub draw: DrawDesc,
+  pub mesh: bool,
+}
+
+// a pass to build, its module is compiled from `source` unless `shader` is given
+#[derive(Debug)]
+pub struct PassBuild {
+  pub pass: PassTarget,
+  pub source: String,
+  pub language: ShaderLanguage,
+  pub shader: Option<Arc<CompiledShader>>,
+  pub entry_points: EntryPoints,
+  // override values by name, the ones the module doesn't declare are dropped
+  pub overrides: HashMap<String, f64>,
+}
+
+// what `GfxState::finish_build` changes besides the passes
+#[derive(Debug)]
+pub enum BuildChange {
+  Shader,
+  Draw(DrawDesc),
+  Mesh(Option<Box<Mesh>>),
+}
+
+// started by one of the `GfxState::begin_*` methods, `run` it and hand the result to
+// `GfxState::finish_build`
+#[derive(Debug)]
+pub struct PipelineBuild {
+  pub builder: PipelineBuilder,
+  pub passes: Vec<PassBuild>,
+  pub change: BuildChange,
+}
+
+#[derive(Debug)]
+pub struct BuiltPass {
+  pub pass: PassTarget,
+  pub shader: Arc<CompiledShader>,
+  pub entry_points: EntryPoints,
+  pub overrides: HashMap<String, f64>,
+  pub pipeline: Pipeline,
+}
+
+#[derive(Debug)]
+pub struct BuiltPipelines {
+  pub passes: Vec<BuiltPass>,
+  pub change: BuildChange,
+}
+
+impl PipelineBuild {
+  // builds every pass, nothing is built when one of them fails
+  pub async fn run(self) -> Result<BuiltPipelines, Error> {
+    let mut passes = Vec::with_capacity(self.passes.len());
+    for pass in self.passes {
+      passes.push(self.builder.build_pass(pass).await.map_err(Error::Pipeline)?);
+    }
+
+    Ok(BuiltPipelines {
+      passes,
+      change: self.change,
+    })
+  }
+
+  // what `run` would report and the annotated parameters of the last pass, empty when a pass
+  // doesn't build
+  pub async fn check(self) -> (wgpu::CompilationInfo, Vec<Parameter>) {
+    let mut parameters = Vec::new();
+    for pass in self.passes {
+      match self.builder.build_pass(pass).await {
+        Ok(built) => parameters = built.shader.parameters.clone(),
+        Err(messages) => return (wgpu::CompilationInfo { messages }, Vec::new()),
+      }
+    }
+
+    (wgpu::CompilationInfo { messages: Vec::new() }, parameters)
+  }
+}
+
+impl PipelineBuilder {
+  async fn build_pass(&self, build: PassBuild) -> Result<BuiltPass, Vec<wgpu::CompilationMessage>> {
+    let shader = match build.shader {
+      Some(shader) => shader,
+      None => Arc::new(self.compile_module(&build.source, build.language, build.pass == PassTarget::Image).await?),
+    };
+    let overrides = shader.override_values(&build.overrides);
+    let pipeline = self.build_pipeline(build.pass, &shader, &build.entry_points, &overrides).await?;
+
+    Ok(BuiltPass {
+      pass: build.pass,
+      shader,
+      entry_points: build.entry_points,
+      overrides,
+      pipeline,
+    })
+  }
+
+  // builds the pipeline inside an error scope, missing entry points, binding mismatches and
+  // wrong output types are only caught here and would otherwise end up in `on_uncaptured_error`
+  async fn build_pipeline(
+    &self,
+    pass: PassTarget,
+    shader: &CompiledShader,
+    entry_points: &EntryPoints,
+    overrides: &HashMap<String, f64>,
+  ) -> Result<Pipeline, Vec<wgpu::CompilationMessage>> {
+    let (format, draw, mesh) = match pass {
+      PassTarget::Image => (self.output_format, self.draw, self.mesh),
+      PassTarget::Buffer(_) => (self.buffer_format, DrawDesc::default(), false),
+    };
+
+    let (vertex_module, vertex_entry_point) = if shader.builtin_vertex {
+      if let Some(name) = entry_points.vertex.as_ref() {
+        return Err(vec![wgpu::CompilationMessage {
+          message: format!("no vertex entry point `{}`, the shader uses the built-in vertex stage", name),
+          message_type: wgpu::CompilationMessageType::Error,
+          location: None,
+        }]);
+      }
+      let module = if mesh { &self.context.mesh_module } else { &self.context.fullscreen_module };
+      (module, String::from("vs_main"))
+    } else {
+      let name = shader.entry_point(naga::ShaderStage::Vertex, entry_points.vertex.as_deref(), "vs_main").map_err(|message| vec![message])?;
+      (&shader.module, name)
+    };
+    let fragment_entry_point = shader.entry_point(naga::ShaderStage::Fragment, entry_points.fragment.as_deref(), "fs_main").map_err(|message| vec![message])?;
+
+    let compute_layout = match self.compute_layout.as_ref() {
+      None if !shader.compute.is_empty() => return Err(vec![wgpu::CompilationMessage {
+        message: Error::Unsupported(String::from("compute entry points are")).to_string(),
+        message_type: wgpu::CompilationMessageType::Error,
+        location: None,
+      }]),
+      layout => layout,
+    };
+
+    let constants = shader.pipeline_constants(overrides);
+    let no_constants = HashMap::new();
+
+    let _scope = self.context.error_scopes.lock().await;
+    self.context.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let mut pipeline = Pipeline::new(&PipelineCreateDesc {
+      device: &self.context.device,
+      layout: &self.layout,
+      format,
+      vertex: ShaderStage {
+        module: vertex_module,
+        entry_point: &vertex_entry_point,
+        constants: if shader.builtin_vertex { &no_constants } else { &constants },
+      },
+      fragment: ShaderStage {
+        module: &shader.module,
+        entry_point: &fragment_entry_point,
+        constants: &constants,
+      },
+      draw: &draw,
+      mesh,
+      cache: self.context.pipeline_cache.as_ref(),
+    });
+
+    if let Some(compute_layout) = compute_layout {
+      pipeline.compute = shader.compute.iter().map(|entry_point| ComputePipeline::new(&ComputePipelineCreateDesc {
+        device: &self.context.device,
+        layout: compute_layout,
+        compute: ShaderStage {
+          module: &shader.module,
+          entry_point: &entry_point.name,
+          constants: &constants,
+        },
+        workgroup_size: entry_point.workgroup_size,
+        invocations: entry_point.invocations,
+        cache: self.context.pipeline_cache.as_ref(),
+      })).collect();
+    }
+
+    match self.context.device.pop_error_scope().await {
+      Some(err) => Err(vec![scope_error_message(err)]),
+      None => Ok(pipeline),
+    }
+  }
+
+  async fn compile_module(&self, shader_source: &str, language: ShaderLanguage, flip_y: bool) -> Result<CompiledShader, Vec<wgpu::CompilationMessage>> {
+    // shaders without their own vertex stage are drawn with the fullscreen one
+    let (naga_module, parsed) = match language {
+      ShaderLanguage::Wgsl => match shader::parse_wgsl(shader_source, flip_y)? {
+        WgslShader::Full(parsed) => (None, parsed),
+        WgslShader::Fragment(module) => (Some(module), None),
+      },
+      ShaderLanguage::Glsl => (Some(shader::translate_glsl(shader_source, flip_y)?), None),
+    };
+
+    let builtin_vertex = naga_module.is_some();
+    let reflect = |module: &naga::Module| {
+      let uniform_layout = UniformLayout::reflect(module);
+      let parameters = parameters::reflect(shader_source, module, uniform_layout.as_ref());
+      let compute = shader::reflect_compute(shader_source, module);
+      let storage = StorageUsage::reflect(module, self.storage_group);
+      (shader::reflect_entry_points(module), uniform_layout, parameters, compute, storage, shader::reflect_overrides(module))
+    };
+    let (entry_points, uniform_layout, parameters, compute, storage, overrides) = match naga_module.as_ref().or(parsed.as_ref()) {
+      Some(module) => reflect(module),
+      // wgpu reports parse errors with locations below, nothing to reflect then
+      None => Default::default(),
+    };
+
+    let scope = self.context.error_scopes.lock().await;
+    self.context.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let module = match naga_module {
+      Some(module) => shader::create_naga_module(&self.context.device, module),
+      None => self.context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+      }),
+    };
+
+    // parse errors have locations, prefer them over the scope's error for the same problem
+    let info = module.get_compilation_info().await;
+    let scope_error = self.context.device.pop_error_scope().await;
+    drop(scope);
+    if info.messages.iter().any(|message| message.message_type == wgpu::CompilationMessageType::Error) {
+      return Err(info.messages);
+    }
+    if let Some(err) = scope_error {
+      return Err(vec![scope_error_message(err)]);
+    }
+
+    Ok(CompiledShader {
+      source: shader_source.to_string(),
+      language,
+      flip_y,
+      module,
+      builtin_vertex,
+      entry_points,
+      uniform_layout,
+      parameters,
+      compute,
+      storage,
+      overrides,
+    })
+  }
+}
+
+fn scope_error_message(err: wgpu::Error) -> wgpu::CompilationMessage {
+  wgpu::CompilationMessage {
+    message: err.to_string(),
+    message_type: wgpu::CompilationMessageType::Error,
+    location: None,
+  }
+}
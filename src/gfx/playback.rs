@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+
+use crate::error::Error;
+
+// transport state of an instance, `GfxState::render` asks it how far to move time each frame
+#[derive(Debug, Clone)]
+pub struct Playback {
+  pub paused: bool,
+  // multiplies the wall clock delta, negative plays backwards
+  pub speed: f32,
+  // time wraps around inside [start, end)
+  pub loop_range: Option<(f32, f32)>,
+  // seconds per frame, replaces the wall clock so renders are reproducible
+  pub fixed_timestep: Option<f32>,
+  // deltas of frames requested by `step`, rendered one per frame even while paused
+  steps: VecDeque<f32>,
+}
+
+impl Default for Playback {
+  fn default() -> Self {
+    Self {
+      paused: false,
+      speed: 1.0,
+      loop_range: None,
+      fixed_timestep: None,
+      steps: VecDeque::new(),
+    }
+  }
+}
+
+impl Playback {
+  pub fn set_speed(&mut self, speed: f32) -> Result<(), Error> {
+    if !speed.is_finite() {
+      return Err(Error::InvalidParameter(format!("playback speed must be finite, got {}", speed)));
+    }
+    self.speed = speed;
+    Ok(())
+  }
+
+  pub fn set_loop(&mut self, loop_range: Option<(f32, f32)>) -> Result<(), Error> {
+    if let Some((start, end)) = loop_range {
+      if !start.is_finite() || !end.is_finite() || end <= start {
+        return Err(Error::InvalidParameter(format!("loop end must be after its start, got {} to {}", start, end)));
+      }
+    }
+    self.loop_range = loop_range;
+    Ok(())
+  }
+
+  // `None` goes back to the wall clock
+  pub fn set_fixed_fps(&mut self, fps: Option<f32>) -> Result<(), Error> {
+    if let Some(fps) = fps {
+      if !fps.is_finite() || fps <= 0.0 {
+        return Err(Error::InvalidParameter(format!("fps must be a positive number, got {}", fps)));
+      }
+    }
+    self.fixed_timestep = fps.map(|fps| 1.0 / fps);
+    Ok(())
+  }
+
+  pub fn fixed_fps(&self) -> Option<f32> {
+    self.fixed_timestep.map(|timestep| 1.0 / timestep)
+  }
+
+  // pauses and renders a single frame advanced by `delta_time`, negative steps back.
+  // defaults to the fixed timestep, or 1/60 without one
+  pub fn step(&mut self, delta_time: Option<f32>) -> Result<(), Error> {
+    let delta_time = delta_time.or(self.fixed_timestep).unwrap_or(1.0 / 60.0);
+    if !delta_time.is_finite() {
+      return Err(Error::InvalidParameter(format!("step must be finite, got {}", delta_time)));
+    }
+    self.paused = true;
+    self.steps.push_back(delta_time);
+    Ok(())
+  }
+
+  // how far to advance time this frame, `None` while paused without pending steps
+  pub fn next_delta(&mut self, wall_delta: f32) -> Option<f32> {
+    if let Some(delta_time) = self.steps.pop_front() {
+      return Some(delta_time);
+    }
+
+    if self.paused {
+      None
+    } else {
+      Some(self.fixed_timestep.unwrap_or(wall_delta) * self.speed)
+    }
+  }
+
+  // brings `time` back into the loop range, time outside of it jumps in
+  pub fn wrap(&self, time: f32) -> f32 {
+    match self.loop_range {
+      Some((start, end)) if time < start || time >= end => start + (time - start).rem_euclid(end - start),
+      _ => time,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn looped(start: f32, end: f32) -> Playback {
+    let mut playback = Playback::default();
+    playback.set_loop(Some((start, end))).unwrap();
+    playback
+  }
+
+  fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+  }
+
+  #[test]
+  fn wraps_inside_the_loop_range() {
+    let playback = looped(1.0, 3.0);
+    assert_close(playback.wrap(1.0), 1.0);
+    assert_close(playback.wrap(2.5), 2.5);
+    // the end belongs to the next loop
+    assert_close(playback.wrap(3.0), 1.0);
+    assert_close(playback.wrap(3.25), 1.25);
+  }
+
+  #[test]
+  fn wraps_negative_time_back_to_the_end() {
+    let playback = looped(1.0, 3.0);
+    assert_close(playback.wrap(0.75), 2.75);
+    assert_close(playback.wrap(-0.5), 1.5);
+
+    let playback = looped(-2.0, 2.0);
+    assert_close(playback.wrap(-2.5), 1.5);
+  }
+
+  #[test]
+  fn plays_a_loop_backwards() {
+    let mut playback = looped(0.0, 1.0);
+    playback.set_speed(-1.0).unwrap();
+    playback.set_fixed_fps(Some(4.0)).unwrap();
+
+    let mut time = 0.5;
+    let mut times = Vec::new();
+    for _ in 0..4 {
+      let delta_time = playback.next_delta(0.0).unwrap();
+      time = playback.wrap(time + delta_time);
+      times.push(time);
+    }
+    assert_eq!(times, vec![0.25, 0.0, 0.75, 0.5]);
+  }
+
+  #[test]
+  fn seeks_outside_the_loop_range_jump_in() {
+    let playback = looped(1.0, 3.0);
+    // 10 is 4.5 loops past the start
+    assert_close(playback.wrap(10.0), 2.0);
+    assert_close(playback.wrap(-9.5), 2.5);
+  }
+
+  #[test]
+  fn leaves_time_alone_without_a_loop() {
+    let playback = Playback::default();
+    assert_eq!(playback.wrap(-5.0), -5.0);
+    assert_eq!(playback.wrap(1e6), 1e6);
+  }
+
+  #[test]
+  fn steps_a_paused_playback_one_frame_at_a_time() {
+    let mut playback = Playback { paused: true, ..Default::default() };
+    assert_eq!(playback.next_delta(0.016), None);
+
+    playback.step(Some(0.5)).unwrap();
+    playback.step(Some(-0.25)).unwrap();
+    assert_eq!(playback.next_delta(0.016), Some(0.5));
+    assert_eq!(playback.next_delta(0.016), Some(-0.25));
+    assert_eq!(playback.next_delta(0.016), None);
+    assert!(playback.paused);
+  }
+
+  #[test]
+  fn steps_by_the_fixed_timestep_and_pauses() {
+    let mut playback = Playback::default();
+    playback.step(None).unwrap();
+    assert!(playback.paused);
+    assert_eq!(playback.next_delta(0.1), Some(1.0 / 60.0));
+
+    playback.set_fixed_fps(Some(24.0)).unwrap();
+    playback.step(None).unwrap();
+    assert_eq!(playback.next_delta(0.1), Some(1.0 / 24.0));
+    assert_eq!(playback.next_delta(0.1), None);
+  }
+
+  #[test]
+  fn steps_ignore_the_speed() {
+    let mut playback = Playback::default();
+    playback.set_speed(-2.0).unwrap();
+    playback.step(Some(0.5)).unwrap();
+    assert_eq!(playback.next_delta(0.1), Some(0.5));
+  }
+
+  #[test]
+  fn rejects_invalid_settings() {
+    let mut playback = Playback::default();
+    assert!(playback.set_speed(f32::NAN).is_err());
+    assert!(playback.set_loop(Some((2.0, 2.0))).is_err());
+    assert!(playback.set_loop(Some((3.0, 1.0))).is_err());
+    assert!(playback.set_fixed_fps(Some(0.0)).is_err());
+    assert!(playback.step(Some(f32::INFINITY)).is_err());
+    assert!(!playback.paused);
+  }
+}
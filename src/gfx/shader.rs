@@ -0,0 +1,327 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use super::{buffer_pass::BUFFERS_WGSL, channels::CHANNELS_WGSL, gfx_state::COMMON_UNIFORMS_WGSL, parameters::{self, Parameter, ParameterKind}, storage::StorageUsage, user_uniforms::UniformLayout};
+
+// built-in vertex stage drawing a single triangle that covers the whole target
+pub const FULLSCREEN_WGSL: &str = include_str!("fullscreen.wgsl");
+
+// declarations shared by every shadertoy style glsl shader, must end with a newline
+const GLSL_PRELUDE: &str = include_str!("shadertoy_prelude.glsl");
+
+// `FragmentInput`, written by the fullscreen vertex stage
+pub const FRAGMENT_INPUT_WGSL: &str = include_str!("fragment_prelude.wgsl");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShaderLanguage {
+  #[default]
+  Wgsl,
+  // shadertoy glsl, the source provides `mainImage(out vec4, in vec2)`
+  Glsl,
+}
+
+// entry points picked by the caller, missing ones are discovered from the module
+#[derive(Debug, Clone, Default)]
+pub struct EntryPoints {
+  pub vertex: Option<String>,
+  pub fragment: Option<String>,
+}
+
+// a compiled module with its reflected entry points, pipelines for other entry points
+// of the same source reuse it
+#[derive(Debug)]
+pub struct CompiledShader {
+  pub source: String,
+  pub language: ShaderLanguage,
+  pub flip_y: bool,
+  pub module: wgpu::ShaderModule,
+  // drawn with the fullscreen vertex stage instead of one from `module`
+  pub builtin_vertex: bool,
+  pub entry_points: Vec<(naga::ShaderStage, String)>,
+  pub uniform_layout: Option<UniformLayout>,
+  pub parameters: Vec<Parameter>,
+  pub compute: Vec<ComputeEntryPoint>,
+  pub storage: StorageUsage,
+  // pipeline constant key of every named `override`, its `@id` when it has one
+  pub overrides: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputeEntryPoint {
+  pub name: String,
+  pub workgroup_size: [u32; 3],
+  // from `@dispatch`, the render size when `None`
+  pub invocations: Option<[u32; 3]>,
+}
+
+impl CompiledShader {
+  pub fn matches(&self, source: &str, language: ShaderLanguage, flip_y: bool) -> bool {
+    self.language == language && self.flip_y == flip_y && self.source == source
+  }
+
+  // the `@default` of every override this shader declares, `previous` values of overrides it
+  // still declares are kept
+  pub fn override_values(&self, previous: &HashMap<String, f64>) -> HashMap<String, f64> {
+    let mut values: HashMap<String, f64> = self.parameters.iter()
+      .filter(|parameter| parameter.kind == ParameterKind::Override)
+      .filter_map(|parameter| Some((parameter.name.clone(), *parameter.default.as_ref()?.first()?)))
+      .collect();
+
+    values.extend(previous.iter()
+      .filter(|(name, _)| self.overrides.contains_key(*name))
+      .map(|(name, value)| (name.clone(), *value)));
+    values
+  }
+
+  // `values` by override name to the constants a pipeline of this shader is built with
+  pub fn pipeline_constants(&self, values: &HashMap<String, f64>) -> HashMap<String, f64> {
+    values.iter()
+      .filter_map(|(name, value)| Some((self.overrides.get(name)?.clone(), *value)))
+      .collect()
+  }
+
+  // the requested entry point, or the only one of `stage`, or `default` when there are several
+  pub fn entry_point(&self, stage: naga::ShaderStage, requested: Option<&str>, default: &str) -> Result<String, wgpu::CompilationMessage> {
+    let names: Vec<&str> = self.entry_points.iter()
+      .filter(|(entry_stage, _)| *entry_stage == stage)
+      .map(|(_, name)| name.as_str())
+      .collect();
+
+    let stage_name = match stage {
+      naga::ShaderStage::Vertex => "vertex",
+      naga::ShaderStage::Fragment => "fragment",
+      naga::ShaderStage::Compute => "compute",
+    };
+
+    let message = match requested {
+      Some(name) if names.contains(&name) => return Ok(name.to_string()),
+      Some(name) if names.is_empty() => format!("no {} entry point `{}`, the shader has none", stage_name, name),
+      Some(name) => format!("no {} entry point `{}`, found: {}", stage_name, name, names.join(", ")),
+      None if names.len() == 1 => return Ok(names[0].to_string()),
+      None if names.contains(&default) => return Ok(default.to_string()),
+      None if names.is_empty() => format!("no {} entry point found", stage_name),
+      None => format!("several {} entry points, pick one of: {}", stage_name, names.join(", ")),
+    };
+
+    Err(wgpu::CompilationMessage {
+      message,
+      message_type: wgpu::CompilationMessageType::Error,
+      location: None,
+    })
+  }
+}
+
+pub fn reflect_entry_points(module: &naga::Module) -> Vec<(naga::ShaderStage, String)> {
+  module.entry_points.iter()
+    .map(|entry_point| (entry_point.stage, entry_point.name.clone()))
+    .collect()
+}
+
+pub fn reflect_compute(source: &str, module: &naga::Module) -> Vec<ComputeEntryPoint> {
+  let dispatch_sizes = parameters::dispatch_sizes(source);
+  module.entry_points.iter()
+    .filter(|entry_point| entry_point.stage == naga::ShaderStage::Compute)
+    .map(|entry_point| ComputeEntryPoint {
+      name: entry_point.name.clone(),
+      workgroup_size: entry_point.workgroup_size,
+      invocations: dispatch_sizes.get(&entry_point.name).copied(),
+    })
+    .collect()
+}
+
+pub fn reflect_overrides(module: &naga::Module) -> HashMap<String, String> {
+  module.overrides.iter()
+    .filter_map(|(_, constant)| {
+      let name = constant.name.clone()?;
+      let key = constant.id.map_or_else(|| name.clone(), |id| id.to_string());
+      Some((name, key))
+    })
+    .collect()
+}
+
+impl ShaderLanguage {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "wgsl" => Some(ShaderLanguage::Wgsl),
+      "glsl" => Some(ShaderLanguage::Glsl),
+      _ => None,
+    }
+  }
+}
+
+// wraps a shadertoy shader into a glsl fragment shader and translates it into naga ir.
+// `flip_y` moves the origin of `fragCoord` to the bottom left, which is only wanted when
+// rendering to the surface, buffer passes keep rows in texture order so sampling them
+// with `fragCoord / iResolution.xy` lines up.
+pub fn translate_glsl(source: &str, flip_y: bool) -> Result<naga::Module, Vec<wgpu::CompilationMessage>> {
+  let frag_coord = if flip_y {
+    "vec2(gl_FragCoord.x, common_uniforms.resolution.y - gl_FragCoord.y)"
+  } else {
+    "gl_FragCoord.xy"
+  };
+
+  let wrapped = format!(
+    "{}{}\nvoid main() {{\n  mainImage(shaderx_frag_color, {});\n}}\n",
+    GLSL_PRELUDE, source, frag_coord,
+  );
+
+  let mut frontend = naga::front::glsl::Frontend::default();
+  let module = frontend
+    .parse(&naga::front::glsl::Options::from(naga::ShaderStage::Fragment), &wrapped)
+    .map_err(|err| {
+      err.errors.iter()
+        .map(|e| error_message(e.kind.to_string(), e.meta, &wrapped, GLSL_PRELUDE, source))
+        .collect::<Vec<_>>()
+    })?;
+
+  validate(module, &wrapped, GLSL_PRELUDE, source)
+}
+
+// how a wgsl source is compiled, decided from the entry points and functions naga finds
+#[derive(Debug)]
+pub enum WgslShader {
+  // has a `@vertex` entry point, compiled as is. the parsed module is `None` when naga can't
+  // parse the source, the device reports the errors then
+  Full(Option<naga::Module>),
+  // fragment-only, validated with the common uniforms, buffers, channels and `FragmentInput`
+  // prepended and drawn with the fullscreen vertex stage
+  Fragment(naga::Module),
+}
+
+// a fragment-only shader provides either `fs_main` or `mainImage(fragCoord: vec2<f32>) -> vec4<f32>`,
+// `flip_y` works like in `translate_glsl`. fragment-only sources use names from the prelude so
+// they only parse with it, when neither parses the errors of the one that got further are kept
+pub fn parse_wgsl(source: &str, flip_y: bool) -> Result<WgslShader, Vec<wgpu::CompilationMessage>> {
+  let own = naga::front::wgsl::parse_str(source);
+  if own.as_ref().is_ok_and(has_vertex_stage) {
+    return Ok(WgslShader::Full(own.ok()));
+  }
+
+  let prelude = format!("{}\n{}\n{}\n{}\n", COMMON_UNIFORMS_WGSL, BUFFERS_WGSL, CHANNELS_WGSL, FRAGMENT_INPUT_WGSL);
+  let wrapped = format!("{}{}", prelude, source);
+  let module = match naga::front::wgsl::parse_str(&wrapped) {
+    Ok(module) if has_vertex_stage(&module) => return Ok(WgslShader::Full(own.ok())),
+    Ok(module) => module,
+    Err(err) => {
+      let own_offset = own.as_ref().err().map(|err| error_offset(err, 0));
+      if own_offset.is_some_and(|offset| offset > error_offset(&err, prelude.len())) {
+        return Ok(WgslShader::Full(None));
+      }
+      return Err(vec![wgsl_error_message(&err, &wrapped, &prelude, source)]);
+    },
+  };
+
+  let main_image = module.functions.iter().any(|(_, function)| function.name.as_deref() == Some("mainImage"));
+  let fragment_entry = module.entry_points.iter().any(|entry_point| entry_point.stage == naga::ShaderStage::Fragment);
+  if !main_image || fragment_entry {
+    return validate(module, &wrapped, &prelude, source).map(WgslShader::Fragment);
+  }
+
+  let frag_coord = if flip_y {
+    "vec2<f32>(shaderx_input.position.x, common_uniforms.resolution.y - shaderx_input.position.y)"
+  } else {
+    "shaderx_input.position.xy"
+  };
+  let wrapped = format!(
+    "{}\n@fragment\nfn fs_main(shaderx_input: FragmentInput) -> @location(0) vec4<f32> {{\n  return mainImage({});\n}}\n",
+    wrapped, frag_coord,
+  );
+  let module = naga::front::wgsl::parse_str(&wrapped).map_err(|err| vec![wgsl_error_message(&err, &wrapped, &prelude, source)])?;
+  validate(module, &wrapped, &prelude, source).map(WgslShader::Fragment)
+}
+
+fn has_vertex_stage(module: &naga::Module) -> bool {
+  module.entry_points.iter().any(|entry_point| entry_point.stage == naga::ShaderStage::Vertex)
+}
+
+// where in the user's source parsing stopped, `prelude_len` bytes of generated code come first
+fn error_offset(err: &naga::front::wgsl::ParseError, prelude_len: usize) -> usize {
+  err.labels()
+    .filter_map(|(span, _)| span.to_range())
+    .map(|range| range.start.saturating_sub(prelude_len))
+    .min()
+    .unwrap_or(0)
+}
+
+fn wgsl_error_message(err: &naga::front::wgsl::ParseError, wrapped: &str, prelude: &str, source: &str) -> wgpu::CompilationMessage {
+  let span = err.labels().next().map(|(span, _)| span).unwrap_or_default();
+  error_message(err.message().to_string(), span, wrapped, prelude, source)
+}
+
+fn validate(module: naga::Module, wrapped: &str, prelude: &str, source: &str) -> Result<naga::Module, Vec<wgpu::CompilationMessage>> {
+  let mut validator = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::default());
+  if let Err(err) = validator.validate(&module) {
+    let span = err.spans().next().map(|(span, _)| *span).unwrap_or_default();
+    return Err(vec![error_message(err.as_inner().to_string(), span, wrapped, prelude, source)]);
+  }
+
+  Ok(module)
+}
+
+pub fn create_naga_module(device: &wgpu::Device, module: naga::Module) -> wgpu::ShaderModule {
+  device.create_shader_module(wgpu::ShaderModuleDescriptor {
+    label: Some("Shader"),
+    source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+  })
+}
+
+// reports locations relative to the user's source, errors in generated code have none
+fn error_message(message: String, span: naga::Span, wrapped: &str, prelude: &str, source: &str) -> wgpu::CompilationMessage {
+  let prelude_lines = prelude.matches('\n').count() as u32;
+  let prelude_len = prelude.len() as u32;
+
+  let source_end = prelude_len + source.len() as u32;
+  let location = match span.is_defined().then(|| span.location(wrapped)) {
+    // naga reports a missing `}` or `;` at the end of input
+    Some(location) if location.offset == source_end => Some(end_location(source)),
+    Some(location) if location.offset >= prelude_len && location.offset < source_end => Some(wgpu::SourceLocation {
+      line_number: location.line_number - prelude_lines,
+      line_position: location.line_position,
+      offset: location.offset - prelude_len,
+      length: location.length,
+    }),
+    _ => None,
+  };
+
+  wgpu::CompilationMessage {
+    message,
+    message_type: wgpu::CompilationMessageType::Error,
+    location,
+  }
+}
+
+// the end of the last line of `source` that isn't blank
+fn end_location(source: &str) -> wgpu::SourceLocation {
+  let end = source.trim_end();
+  let line_start = end.rfind('\n').map_or(0, |index| index + 1);
+  wgpu::SourceLocation {
+    line_number: end.matches('\n').count() as u32 + 1,
+    line_position: end[line_start..].chars().count() as u32 + 1,
+    offset: end.len() as u32,
+    length: 0,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn error_location(source: &str) -> Option<wgpu::SourceLocation> {
+    let messages = parse_wgsl(source, false).expect_err("the shader shouldn't parse");
+    messages[0].location
+  }
+
+  #[test]
+  fn locates_errors_in_the_source() {
+    let location = error_location("fn mainImage(frag_coord: vec2<f32>) -> vec4<f32> {\n  return vec4<f32>(1.0) +;\n}\n").expect("a location");
+    assert_eq!(location.line_number, 2);
+  }
+
+  #[test]
+  fn locates_errors_at_the_end_of_input() {
+    let location = error_location("fn mainImage(frag_coord: vec2<f32>) -> vec4<f32> {\n  return vec4<f32>(1.0);\n\n").expect("a location");
+    assert_eq!((location.line_number, location.line_position, location.offset), (2, 25, 75));
+
+    let location = error_location("fn mainImage(frag_coord: vec2<f32>) -> vec4<f32> {").expect("a location");
+    assert_eq!((location.line_number, location.line_position), (1, 51));
+  }
+}
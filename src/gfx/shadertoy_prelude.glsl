@@ -0,0 +1,55 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform CommonUniforms {
+  float time;
+  float delta_time;
+  uint frame;
+  float seed;
+  vec4 mouse;
+  vec2 resolution;
+  float pixel_ratio;
+  float aspect;
+  vec4 date;
+  vec4 channel_resolution[4];
+  uint vertex_count;
+  uint instance_count;
+  mat4 model;
+  mat4 view;
+  mat4 projection;
+  vec4 camera_position;
+} common_uniforms;
+
+layout(set = 1, binding = 0) uniform texture2D buffer_a;
+layout(set = 1, binding = 1) uniform texture2D buffer_b;
+layout(set = 1, binding = 2) uniform texture2D buffer_c;
+layout(set = 1, binding = 3) uniform texture2D buffer_d;
+layout(set = 1, binding = 4) uniform sampler buffer_sampler;
+
+layout(set = 2, binding = 0) uniform texture2D channel0;
+layout(set = 2, binding = 1) uniform texture2D channel1;
+layout(set = 2, binding = 2) uniform texture2D channel2;
+layout(set = 2, binding = 3) uniform texture2D channel3;
+layout(set = 2, binding = 4) uniform sampler channel0_sampler;
+layout(set = 2, binding = 5) uniform sampler channel1_sampler;
+layout(set = 2, binding = 6) uniform sampler channel2_sampler;
+layout(set = 2, binding = 7) uniform sampler channel3_sampler;
+
+layout(location = 0) out vec4 shaderx_frag_color;
+
+#define iTime common_uniforms.time
+#define iTimeDelta common_uniforms.delta_time
+#define iFrameRate (1.0 / max(common_uniforms.delta_time, 0.0001))
+#define iFrame int(common_uniforms.frame)
+#define iMouse common_uniforms.mouse
+#define iResolution vec3(common_uniforms.resolution, 1.0)
+#define iDate common_uniforms.date
+#define iChannelResolution common_uniforms.channel_resolution
+#define iChannel0 sampler2D(channel0, channel0_sampler)
+#define iChannel1 sampler2D(channel1, channel1_sampler)
+#define iChannel2 sampler2D(channel2, channel2_sampler)
+#define iChannel3 sampler2D(channel3, channel3_sampler)
+#define iBufferA sampler2D(buffer_a, buffer_sampler)
+#define iBufferB sampler2D(buffer_b, buffer_sampler)
+#define iBufferC sampler2D(buffer_c, buffer_sampler)
+#define iBufferD sampler2D(buffer_d, buffer_sampler)
+
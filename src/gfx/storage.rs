@@ -0,0 +1,214 @@
+use crate::error::Error;
+use super::buffer_pass::MAX_BUFFER_PASSES;
+
+// storage buffers and textures shared by every pass, bound at the buffer group after the
+// buffer textures and their sampler. compute entry points write them, the other stages
+// read them:
+//   @group(1) @binding(5) var<storage, read_write> particles: array<Particle>;  // compute
+//   @group(1) @binding(5) var<storage, read> particles: array<Particle>;        // fragment
+//   @group(1) @binding(9) var trails: texture_storage_2d<rgba16float, write>;   // compute
+//   @group(1) @binding(9) var trails: texture_2d<f32>;                          // fragment
+pub const MAX_STORAGE_BUFFERS: usize = 4;
+pub const MAX_STORAGE_TEXTURES: usize = 4;
+pub const STORAGE_BUFFER_BINDING: u32 = MAX_BUFFER_PASSES as u32 + 1;
+pub const STORAGE_TEXTURE_BINDING: u32 = STORAGE_BUFFER_BINDING + MAX_STORAGE_BUFFERS as u32;
+pub const STORAGE_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// size of buffers that no shader declares
+const EMPTY_BUFFER_SIZE: u64 = 16;
+
+// the storage a shader declares, buffers by the size of their type. runtime sized arrays
+// count as a single element, `Storage::set_buffer_size` makes room for more
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+  pub buffers: [Option<u64>; MAX_STORAGE_BUFFERS],
+  pub textures: [bool; MAX_STORAGE_TEXTURES],
+}
+
+impl StorageUsage {
+  pub fn reflect(module: &naga::Module, group: u32) -> Self {
+    let mut usage = Self::default();
+
+    for (_, variable) in module.global_variables.iter() {
+      let Some(binding) = variable.binding.as_ref().filter(|binding| binding.group == group) else {
+        continue;
+      };
+
+      let buffer = binding.binding.checked_sub(STORAGE_BUFFER_BINDING).map(|index| index as usize);
+      let texture = binding.binding.checked_sub(STORAGE_TEXTURE_BINDING).map(|index| index as usize);
+      match (buffer, texture) {
+        (_, Some(index)) if index < MAX_STORAGE_TEXTURES => usage.textures[index] = true,
+        (Some(index), _) if index < MAX_STORAGE_BUFFERS => {
+          let size = module.types[variable.ty].inner.size(module.to_ctx()) as u64;
+          usage.buffers[index] = Some(usage.buffers[index].unwrap_or(0).max(size));
+        },
+        _ => {},
+      }
+    }
+
+    usage
+  }
+
+  pub fn merge(&mut self, other: &StorageUsage) {
+    for (size, other) in self.buffers.iter_mut().zip(other.buffers.iter()) {
+      *size = (*size).max(*other);
+    }
+    for (used, other) in self.textures.iter_mut().zip(other.textures.iter()) {
+      *used |= *other;
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct Storage {
+  // sizes set by the host, buffers still grow to what the shaders declare
+  buffer_sizes: [Option<u64>; MAX_STORAGE_BUFFERS],
+  // textures the shaders use follow the render size without one
+  texture_sizes: [Option<(u32, u32)>; MAX_STORAGE_TEXTURES],
+  usage: StorageUsage,
+  render_size: (u32, u32),
+  max_buffer_size: u64,
+  max_dimension: u32,
+  buffers: [wgpu::Buffer; MAX_STORAGE_BUFFERS],
+  textures: [wgpu::Texture; MAX_STORAGE_TEXTURES],
+  views: [wgpu::TextureView; MAX_STORAGE_TEXTURES],
+}
+
+#[derive(Debug)]
+pub struct StorageCreateDesc<'a> {
+  pub device: &'a wgpu::Device,
+  pub limits: &'a wgpu::Limits,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Storage {
+  pub fn new(create_desc: &StorageCreateDesc) -> Self {
+    let device = create_desc.device;
+    let textures: [wgpu::Texture; MAX_STORAGE_TEXTURES] = std::array::from_fn(|_| create_texture(device, 1, 1));
+
+    Self {
+      buffer_sizes: [None; MAX_STORAGE_BUFFERS],
+      texture_sizes: [None; MAX_STORAGE_TEXTURES],
+      usage: StorageUsage::default(),
+      render_size: (create_desc.width, create_desc.height),
+      max_buffer_size: (create_desc.limits.max_storage_buffer_binding_size as u64).min(create_desc.limits.max_buffer_size),
+      max_dimension: create_desc.limits.max_texture_dimension_2d,
+      buffers: std::array::from_fn(|_| create_buffer(device, EMPTY_BUFFER_SIZE)),
+      views: std::array::from_fn(|i| textures[i].create_view(&wgpu::TextureViewDescriptor::default())),
+      textures,
+    }
+  }
+
+  pub fn buffer(&self, index: usize) -> &wgpu::Buffer {
+    &self.buffers[index]
+  }
+
+  pub fn view(&self, index: usize) -> &wgpu::TextureView {
+    &self.views[index]
+  }
+
+  // `None` goes back to the size the shaders declare, resizing clears the buffer
+  pub fn set_buffer_size(&mut self, device: &wgpu::Device, index: usize, size: Option<u64>) -> Result<(), Error> {
+    if index >= MAX_STORAGE_BUFFERS {
+      return Err(Error::InvalidParameter(format!("storage buffer index out of range: {}", index)));
+    }
+    if let Some(size) = size {
+      if size == 0 || size % 4 != 0 || size > self.max_buffer_size {
+        return Err(Error::InvalidParameter(format!("storage buffer size must be a multiple of 4 between 4 and {}, got {}", self.max_buffer_size, size)));
+      }
+    }
+
+    self.buffer_sizes[index] = size;
+    self.update(device);
+    Ok(())
+  }
+
+  // `None` follows the render size, resizing clears the texture
+  pub fn set_texture_size(&mut self, device: &wgpu::Device, index: usize, size: Option<(u32, u32)>) -> Result<(), Error> {
+    if index >= MAX_STORAGE_TEXTURES {
+      return Err(Error::InvalidParameter(format!("storage texture index out of range: {}", index)));
+    }
+    if let Some((width, height)) = size {
+      if width == 0 || height == 0 || width > self.max_dimension || height > self.max_dimension {
+        return Err(Error::InvalidParameter(format!("storage texture size must be between 1x1 and {0}x{0}, got {1}x{2}", self.max_dimension, width, height)));
+      }
+    }
+
+    self.texture_sizes[index] = size;
+    self.update(device);
+    Ok(())
+  }
+
+  pub fn set_usage(&mut self, device: &wgpu::Device, usage: StorageUsage) {
+    self.usage = usage;
+    self.update(device);
+  }
+
+  pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+    self.render_size = (width, height);
+    self.update(device);
+  }
+
+  // starts every buffer and texture over from zeros
+  pub fn clear(&mut self, device: &wgpu::Device) {
+    for buffer in self.buffers.iter_mut() {
+      *buffer = create_buffer(device, buffer.size());
+    }
+    for (texture, view) in self.textures.iter_mut().zip(self.views.iter_mut()) {
+      *texture = create_texture(device, texture.width(), texture.height());
+      *view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    }
+  }
+
+  // recreates what changed size, the rest keeps its contents
+  fn update(&mut self, device: &wgpu::Device) {
+    for i in 0..MAX_STORAGE_BUFFERS {
+      let size = match (self.buffer_sizes[i], self.usage.buffers[i]) {
+        (None, None) => EMPTY_BUFFER_SIZE,
+        (configured, declared) => configured.max(declared).unwrap_or_default().min(self.max_buffer_size).next_multiple_of(4),
+      };
+      if self.buffers[i].size() != size {
+        self.buffers[i] = create_buffer(device, size);
+      }
+    }
+
+    for i in 0..MAX_STORAGE_TEXTURES {
+      let (width, height) = match self.texture_sizes[i] {
+        Some(size) => size,
+        None if self.usage.textures[i] => (self.render_size.0.max(1), self.render_size.1.max(1)),
+        None => (1, 1),
+      };
+      if self.textures[i].width() != width || self.textures[i].height() != height {
+        self.textures[i] = create_texture(device, width, height);
+        self.views[i] = self.textures[i].create_view(&wgpu::TextureViewDescriptor::default());
+      }
+    }
+  }
+}
+
+fn create_buffer(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+  device.create_buffer(&wgpu::BufferDescriptor {
+    label: Some("storage buffer"),
+    size,
+    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+    mapped_at_creation: false,
+  })
+}
+
+fn create_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+  device.create_texture(&wgpu::TextureDescriptor {
+    label: Some("storage texture"),
+    size: wgpu::Extent3d {
+      width,
+      height,
+      depth_or_array_layers: 1,
+    },
+    mip_level_count: 1,
+    sample_count: 1,
+    dimension: wgpu::TextureDimension::D2,
+    format: STORAGE_TEXTURE_FORMAT,
+    usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+    view_formats: &[],
+  })
+}
@@ -31,7 +31,7 @@ impl UniformBuffer {
       entries: &[
         wgpu::BindGroupLayoutEntry {
           binding: create_desc.binding,
-          visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+          visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
           ty: wgpu::BindingType::Buffer {
             ty: wgpu::BufferBindingType::Uniform,
             has_dynamic_offset: false,
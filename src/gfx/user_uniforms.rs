@@ -0,0 +1,447 @@
+use crate::error::Error;
+
+// shaders declare their own uniforms at @group(3) @binding(0), e.g.
+//   struct Params { speed: f32, tint: vec3<f32> };
+//   @group(3) @binding(0) var<uniform> params: Params;
+pub const USER_UNIFORMS_GROUP: u32 = 3;
+pub const USER_UNIFORMS_BINDING: u32 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformScalar {
+  F32,
+  I32,
+  U32,
+}
+
+// a scalar, vector or matrix inside the user uniform struct, nested structs and arrays
+// are flattened into `outer.inner` and `name[i]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformField {
+  pub name: String,
+  pub offset: u32,
+  pub scalar: UniformScalar,
+  // 1 for scalars and vectors
+  pub columns: u32,
+  // vector size, 1 for scalars
+  pub rows: u32,
+  // the struct and member it was declared as, `None` for a uniform that isn't a struct.
+  // array elements share the declaration of the array
+  pub declaration: (Option<String>, String),
+}
+
+impl UniformField {
+  pub fn type_name(&self) -> String {
+    let scalar = match self.scalar {
+      UniformScalar::F32 => "f32",
+      UniformScalar::I32 => "i32",
+      UniformScalar::U32 => "u32",
+    };
+
+    match (self.columns, self.rows) {
+      (1, 1) => scalar.to_string(),
+      (1, rows) => format!("vec{}<{}>", rows, scalar),
+      (columns, rows) => format!("mat{}x{}<{}>", columns, rows, scalar),
+    }
+  }
+
+  // wgsl alignment rules, vec3 aligns like vec4 and matrices like their columns
+  pub fn align(&self) -> u32 {
+    match self.rows {
+      1 => 4,
+      2 => 8,
+      _ => 16,
+    }
+  }
+
+  pub fn size(&self) -> u32 {
+    if self.columns == 1 {
+      self.rows * 4
+    } else {
+      self.columns * self.align().max(self.rows * 4)
+    }
+  }
+
+  // number of values expected by `UserUniforms::set`, matrices are column major
+  pub fn len(&self) -> usize {
+    (self.columns * self.rows) as usize
+  }
+
+  fn write(&self, data: &mut [u8], values: &[f64]) {
+    let column_stride = self.align().max(self.rows * 4);
+
+    for (i, value) in values.iter().enumerate() {
+      let column = i as u32 / self.rows;
+      let row = i as u32 % self.rows;
+      let offset = (self.offset + column * column_stride + row * 4) as usize;
+
+      let bytes = match self.scalar {
+        UniformScalar::F32 => (*value as f32).to_le_bytes(),
+        UniformScalar::I32 => (*value as i32).to_le_bytes(),
+        UniformScalar::U32 => (*value as u32).to_le_bytes(),
+      };
+      data[offset..offset + 4].copy_from_slice(&bytes);
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UniformLayout {
+  pub size: u32,
+  pub fields: Vec<UniformField>,
+}
+
+impl UniformLayout {
+  // layout of the uniform at the reserved group and binding, if the shader declares one
+  pub fn reflect(module: &naga::Module) -> Option<Self> {
+    let (_, variable) = module.global_variables.iter().find(|(_, variable)| {
+      variable.space == naga::AddressSpace::Uniform
+        && variable.binding == Some(naga::ResourceBinding { group: USER_UNIFORMS_GROUP, binding: USER_UNIFORMS_BINDING })
+    })?;
+
+    let mut fields = Vec::new();
+    match &module.types[variable.ty].inner {
+      naga::TypeInner::Struct { .. } => add_members(module, variable.ty, None, 0, &mut fields),
+      _ => {
+        let name = variable.name.clone().unwrap_or_default();
+        add_fields(module, variable.ty, name.clone(), (None, name), 0, &mut fields);
+      },
+    }
+
+    Some(Self {
+      size: module.types[variable.ty].inner.size(module.to_ctx()),
+      fields,
+    })
+  }
+
+  pub fn field(&self, name: &str) -> Option<&UniformField> {
+    self.fields.iter().find(|field| field.name == name)
+  }
+
+  // writes `values` for the field `name` into `data`, returns false if there's no such field
+  fn write(&self, data: &mut [u8], name: &str, values: &[f64]) -> Result<bool, Error> {
+    let Some(field) = self.field(name) else {
+      return Ok(false);
+    };
+
+    if values.len() != field.len() {
+      return Err(Error::InvalidParameter(format!("`{}` is a {} and takes {} values, got {}", name, field.type_name(), field.len(), values.len())));
+    }
+
+    field.write(data, values);
+    Ok(true)
+  }
+
+  // copies the values of fields that exist with the same type in both layouts
+  fn copy_values(&self, data: &mut [u8], previous: &UniformLayout, previous_data: &[u8]) {
+    for field in self.fields.iter() {
+      let Some(old) = previous.field(&field.name) else {
+        continue;
+      };
+
+      if old.scalar == field.scalar && old.columns == field.columns && old.rows == field.rows {
+        let size = field.size() as usize;
+        let (from, to) = (old.offset as usize, field.offset as usize);
+        data[to..to + size].copy_from_slice(&previous_data[from..from + size]);
+      }
+    }
+  }
+}
+
+// adds the members of the struct `ty`, prefixed with `prefix.`
+fn add_members(module: &naga::Module, ty: naga::Handle<naga::Type>, prefix: Option<&str>, offset: u32, fields: &mut Vec<UniformField>) {
+  let naga::TypeInner::Struct { members, .. } = &module.types[ty].inner else {
+    return;
+  };
+
+  for member in members.iter() {
+    let member_name = member.name.clone().unwrap_or_default();
+    let name = match prefix {
+      Some(prefix) => format!("{}.{}", prefix, member_name),
+      None => member_name.clone(),
+    };
+    let declaration = (module.types[ty].name.clone(), member_name);
+    add_fields(module, member.ty, name, declaration, offset + member.offset, fields);
+  }
+}
+
+fn add_fields(
+  module: &naga::Module,
+  ty: naga::Handle<naga::Type>,
+  name: String,
+  declaration: (Option<String>, String),
+  offset: u32,
+  fields: &mut Vec<UniformField>,
+) {
+  let scalar = |scalar: &naga::Scalar| match (scalar.kind, scalar.width) {
+    (naga::ScalarKind::Float, 4) => Some(UniformScalar::F32),
+    (naga::ScalarKind::Sint, 4) => Some(UniformScalar::I32),
+    (naga::ScalarKind::Uint, 4) => Some(UniformScalar::U32),
+    _ => None,
+  };
+
+  let (kind, columns, rows) = match &module.types[ty].inner {
+    naga::TypeInner::Scalar(s) => (scalar(s), 1, 1),
+    naga::TypeInner::Vector { size, scalar: s } => (scalar(s), 1, *size as u32),
+    naga::TypeInner::Matrix { columns, rows, scalar: s } => (scalar(s), *columns as u32, *rows as u32),
+    naga::TypeInner::Struct { .. } => {
+      add_members(module, ty, Some(&name), offset, fields);
+      return;
+    },
+    naga::TypeInner::Array { base, size: naga::ArraySize::Constant(count), stride } => {
+      for i in 0..count.get() {
+        add_fields(module, *base, format!("{}[{}]", name, i), declaration.clone(), offset + i * stride, fields);
+      }
+      return;
+    },
+    _ => (None, 0, 0),
+  };
+
+  // other types can't be set from the host, the shader still compiles
+  if let Some(scalar) = kind {
+    fields.push(UniformField { name, offset, scalar, columns, rows, declaration });
+  }
+}
+
+#[derive(Debug)]
+pub struct UserUniforms {
+  pub layout: UniformLayout,
+  pub bind_group: wgpu::BindGroup,
+  buffer: wgpu::Buffer,
+  data: Vec<u8>,
+  dirty: bool,
+}
+
+impl UserUniforms {
+  pub fn new(device: &wgpu::Device, bindings: &UserUniformBindings, layout: UniformLayout) -> Self {
+    let size = layout.size.div_ceil(16).max(1) * 16;
+    let buffer = create_buffer(device, size);
+    let bind_group = bindings.create_bind_group(device, &buffer);
+
+    Self {
+      layout,
+      bind_group,
+      buffer,
+      data: vec![0; size as usize],
+      dirty: true,
+    }
+  }
+
+  // keeps the values of fields that exist with the same type in both layouts
+  pub fn copy_values(&mut self, previous: &UserUniforms) {
+    self.layout.copy_values(&mut self.data, &previous.layout, &previous.data);
+    self.dirty = true;
+  }
+
+  // returns false if the shader has no field called `name`
+  pub fn set(&mut self, name: &str, values: &[f64]) -> Result<bool, Error> {
+    let written = self.layout.write(&mut self.data, name, values)?;
+    self.dirty |= written;
+    Ok(written)
+  }
+
+  pub fn upload(&mut self, queue: &wgpu::Queue) {
+    if self.dirty {
+      queue.write_buffer(&self.buffer, 0, &self.data);
+      self.dirty = false;
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct UserUniformBindings {
+  pub bind_group_layout: wgpu::BindGroupLayout,
+  empty_bind_group: wgpu::BindGroup,
+  pub group: u32,
+}
+
+impl UserUniformBindings {
+  pub fn new(device: &wgpu::Device, group: u32) -> Self {
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: USER_UNIFORMS_BINDING,
+          visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+          },
+          count: None,
+        },
+      ],
+      label: Some("user uniforms bind group layout"),
+    });
+
+    // the bind group keeps the buffer alive
+    let empty_buffer = create_buffer(device, 16);
+    let empty_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("empty user uniforms bind group"),
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: USER_UNIFORMS_BINDING,
+          resource: empty_buffer.as_entire_binding(),
+        },
+      ],
+      layout: &bind_group_layout,
+    });
+
+    Self {
+      bind_group_layout,
+      empty_bind_group,
+      group,
+    }
+  }
+
+  // the bind group of `uniforms`, or the empty one for shaders without user uniforms
+  pub fn bind_group<'a>(&'a self, uniforms: Option<&'a UserUniforms>) -> &'a wgpu::BindGroup {
+    uniforms.map(|uniforms| &uniforms.bind_group).unwrap_or(&self.empty_bind_group)
+  }
+
+  fn create_bind_group(&self, device: &wgpu::Device, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("user uniforms bind group"),
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: USER_UNIFORMS_BINDING,
+          resource: buffer.as_entire_binding(),
+        },
+      ],
+      layout: &self.bind_group_layout,
+    })
+  }
+}
+
+fn create_buffer(device: &wgpu::Device, size: u32) -> wgpu::Buffer {
+  device.create_buffer(&wgpu::BufferDescriptor {
+    label: Some("user uniforms buffer"),
+    size: size as u64,
+    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    mapped_at_creation: false,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PARAMS_WGSL: &str = "
+    struct Light {
+      color: vec3<f32>,
+      intensity: f32,
+    }
+
+    struct Params {
+      speed: f32,
+      tint: vec3<f32>,
+      count: i32,
+      light: Light,
+      offsets: array<vec4<f32>, 2>,
+      basis: mat3x3<f32>,
+      flags: vec2<u32>,
+    }
+
+    @group(3) @binding(0) var<uniform> params: Params;
+  ";
+
+  fn reflect(source: &str) -> UniformLayout {
+    let module = naga::front::wgsl::parse_str(source).expect("the test shader should parse");
+    UniformLayout::reflect(&module).expect("the test shader declares user uniforms")
+  }
+
+  fn floats(data: &[u8], offset: usize, count: usize) -> Vec<f32> {
+    data[offset..offset + count * 4].chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect()
+  }
+
+  #[test]
+  fn reflects_offsets_and_sizes() {
+    let layout = reflect(PARAMS_WGSL);
+    let fields: Vec<(&str, u32, u32, String)> = layout.fields.iter()
+      .map(|field| (field.name.as_str(), field.offset, field.size(), field.type_name()))
+      .collect();
+
+    assert_eq!(fields, vec![
+      ("speed", 0, 4, String::from("f32")),
+      ("tint", 16, 12, String::from("vec3<f32>")),
+      ("count", 28, 4, String::from("i32")),
+      ("light.color", 32, 12, String::from("vec3<f32>")),
+      ("light.intensity", 44, 4, String::from("f32")),
+      ("offsets[0]", 48, 16, String::from("vec4<f32>")),
+      ("offsets[1]", 64, 16, String::from("vec4<f32>")),
+      ("basis", 80, 48, String::from("mat3x3<f32>")),
+      ("flags", 128, 8, String::from("vec2<u32>")),
+    ]);
+    assert_eq!(layout.size, 144);
+
+    let light = layout.field("light.color").unwrap();
+    assert_eq!(light.declaration, (Some(String::from("Light")), String::from("color")));
+    let offset = layout.field("offsets[1]").unwrap();
+    assert_eq!(offset.declaration, (Some(String::from("Params")), String::from("offsets")));
+  }
+
+  #[test]
+  fn reflects_a_uniform_that_isnt_a_struct() {
+    let layout = reflect("@group(3) @binding(0) var<uniform> strength: vec2<f32>;");
+    assert_eq!(layout.fields.len(), 1);
+    assert_eq!(layout.fields[0].name, "strength");
+    assert_eq!(layout.fields[0].declaration, (None, String::from("strength")));
+    assert_eq!(layout.size, 8);
+  }
+
+  #[test]
+  fn writes_values_at_their_offsets() {
+    let layout = reflect(PARAMS_WGSL);
+    let mut data = vec![0; layout.size as usize];
+
+    assert!(layout.write(&mut data, "tint", &[0.25, 0.5, 0.75]).unwrap());
+    assert_eq!(floats(&data, 16, 3), vec![0.25, 0.5, 0.75]);
+
+    assert!(layout.write(&mut data, "count", &[-3.0]).unwrap());
+    assert_eq!(i32::from_le_bytes(data[28..32].try_into().unwrap()), -3);
+
+    assert!(layout.write(&mut data, "flags", &[1.0, 7.0]).unwrap());
+    assert_eq!(u32::from_le_bytes(data[132..136].try_into().unwrap()), 7);
+
+    // columns of a mat3x3 are padded to 16 bytes
+    assert!(layout.write(&mut data, "basis", &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]).unwrap());
+    assert_eq!(floats(&data, 80, 12), vec![1.0, 2.0, 3.0, 0.0, 4.0, 5.0, 6.0, 0.0, 7.0, 8.0, 9.0, 0.0]);
+
+    assert!(layout.write(&mut data, "offsets[1]", &[1.0, 2.0, 3.0, 4.0]).unwrap());
+    assert_eq!(floats(&data, 48, 8), vec![0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0]);
+  }
+
+  #[test]
+  fn rejects_unknown_fields_and_wrong_lengths() {
+    let layout = reflect(PARAMS_WGSL);
+    let mut data = vec![0; layout.size as usize];
+
+    assert!(!layout.write(&mut data, "missing", &[1.0]).unwrap());
+    assert!(layout.write(&mut data, "tint", &[1.0, 2.0]).is_err());
+    assert!(data.iter().all(|byte| *byte == 0));
+  }
+
+  #[test]
+  fn copies_values_across_recompiles() {
+    let previous = reflect(PARAMS_WGSL);
+    let mut previous_data = vec![0; previous.size as usize];
+    previous.write(&mut previous_data, "speed", &[2.5]).unwrap();
+    previous.write(&mut previous_data, "tint", &[0.25, 0.5, 0.75]).unwrap();
+    previous.write(&mut previous_data, "light.intensity", &[4.0]).unwrap();
+
+    // `speed` moves, `tint` changes type and `light` is gone
+    let layout = reflect("
+      struct Params {
+        scale: vec2<f32>,
+        tint: vec4<f32>,
+        speed: f32,
+      }
+
+      @group(3) @binding(0) var<uniform> params: Params;
+    ");
+    let mut data = vec![0; layout.size as usize];
+    layout.copy_values(&mut data, &previous, &previous_data);
+
+    assert_eq!(floats(&data, 32, 1), vec![2.5]);
+    assert_eq!(floats(&data, 16, 4), vec![0.0; 4]);
+    assert_eq!(floats(&data, 0, 2), vec![0.0; 2]);
+  }
+}
@@ -0,0 +1,56 @@
+use std::{collections::HashSet, path::PathBuf, sync::mpsc};
+
+use notify::{RecursiveMode, Watcher};
+
+// watches the directories of the given files, editors often replace a file on save
+// which drops a watch on the file itself
+pub struct HotReload {
+  watcher: notify::RecommendedWatcher,
+  directories: HashSet<PathBuf>,
+  receiver: mpsc::Receiver<PathBuf>,
+}
+
+impl HotReload {
+  pub fn new() -> notify::Result<Self> {
+    let (sender, receiver) = mpsc::channel();
+
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+      match event {
+        Ok(event) if event.kind.is_modify() || event.kind.is_create() => {
+          for path in event.paths.iter().filter_map(|path| path.canonicalize().ok()) {
+            let _ = sender.send(path);
+          }
+        },
+        Ok(_) => {},
+        Err(err) => log::error!("[reload] watch error: {}", err),
+      }
+    })?;
+
+    Ok(Self {
+      watcher,
+      directories: HashSet::new(),
+      receiver,
+    })
+  }
+
+  // paths changed since the last call, canonicalized
+  pub fn changed_files(&self) -> Vec<PathBuf> {
+    self.receiver.try_iter().collect()
+  }
+
+  // starts watching the directories of `files` that aren't watched yet
+  pub fn watch(&mut self, files: &[PathBuf]) {
+    for directory in files.iter().filter_map(|file| file.parent()) {
+      if self.directories.contains(directory) {
+        continue;
+      }
+
+      match self.watcher.watch(directory, RecursiveMode::NonRecursive) {
+        Ok(()) => {
+          self.directories.insert(directory.to_path_buf());
+        },
+        Err(err) => log::error!("[reload] failed to watch {}: {}", directory.display(), err),
+      }
+    }
+  }
+}
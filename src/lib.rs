@@ -2,7 +2,12 @@
 use wasm_bindgen::prelude::wasm_bindgen;
 
 mod app;
+mod error;
 mod gfx;
+#[cfg(not(target_arch = "wasm32"))]
+mod hot_reload;
+#[cfg(not(target_arch = "wasm32"))]
+mod shader_files;
 
 #[wasm_bindgen(start)]
 pub async fn init() {
@@ -16,11 +21,13 @@ pub async fn init() {
   }
 }
 
+// the floor every device is created with, `EventHandler::capabilities` has the device's limit
 #[wasm_bindgen(js_name = getMaxDimension2D)]
 pub fn get_max_dimension_2d() -> u32 {
   let limits = {
+    // native devices start from the downlevel limits, see `GfxContext::new`
     #[cfg(not(target_arch = "wasm32"))] {
-      wgpu::Limits::default()
+      wgpu::Limits::downlevel_defaults()
     }
     #[cfg(target_arch = "wasm32")] {
       wgpu::Limits::downlevel_webgl2_defaults()
@@ -30,3 +37,23 @@ pub fn get_max_dimension_2d() -> u32 {
   limits.max_texture_dimension_2d
 }
 
+
+#[wasm_bindgen(js_name = getCommonUniformsDeclaration)]
+pub fn get_common_uniforms_declaration() -> String {
+  String::from(gfx::gfx_state::COMMON_UNIFORMS_WGSL)
+}
+
+#[wasm_bindgen(js_name = getBuffersDeclaration)]
+pub fn get_buffers_declaration() -> String {
+  String::from(gfx::buffer_pass::BUFFERS_WGSL)
+}
+
+#[wasm_bindgen(js_name = getChannelsDeclaration)]
+pub fn get_channels_declaration() -> String {
+  String::from(gfx::channels::CHANNELS_WGSL)
+}
+
+#[wasm_bindgen(js_name = getFragmentInputDeclaration)]
+pub fn get_fragment_input_declaration() -> String {
+  String::from(gfx::shader::FRAGMENT_INPUT_WGSL)
+}
@@ -1,9 +1,15 @@
+use clap::Parser;
 use shaderx_wgpu::init;
 mod app;
+mod cli;
+mod error;
 mod gfx;
+mod hot_reload;
+mod shader_files;
 
 fn main() {
   pollster::block_on(init());
 
-  let _event_handler = app::EventHandler::new();
+  let cli = cli::Cli::parse();
+  std::process::exit(cli::run(cli));
 }
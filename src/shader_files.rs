@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::gfx::{buffer_pass::PassTarget, gfx_state::GfxState, pipeline::DrawDesc, shader::ShaderLanguage};
+
+// shaders and images loaded from disk by the native app and the cli
+#[derive(Debug, Default, Clone)]
+pub struct ShaderFiles {
+  pub passes: Vec<(PassTarget, PathBuf)>,
+  pub channels: Vec<(usize, PathBuf)>,
+  pub uniforms: Vec<(String, Vec<f64>)>,
+  pub overrides: Vec<(String, f64)>,
+  // fixed timestep, the wall clock drives time without it
+  pub fps: Option<f32>,
+  // sizes in bytes and pixels, the rest is sized from the shaders
+  pub storage_buffers: Vec<(usize, u64)>,
+  pub storage_textures: Vec<(usize, (u32, u32))>,
+  // obj or binary gltf drawn by the image pass
+  pub mesh: Option<PathBuf>,
+  pub draw: DrawDesc,
+}
+
+impl ShaderFiles {
+  // loads every channel and pass, returns false if any of them failed
+  pub async fn load(&self, gfx: &mut GfxState) -> bool {
+    let mut ok = true;
+
+    if let Err(err) = gfx.set_fixed_fps(self.fps) {
+      eprintln!("error: {}", err);
+      ok = false;
+    }
+
+    // before the passes, so the image pipeline is only built once
+    if let Err(err) = gfx.set_draw(self.draw).await {
+      eprintln!("error: {}", err);
+      ok = false;
+    }
+    if let Some(path) = self.mesh.as_ref() {
+      if let Err(err) = gfx.set_mesh_from_file(path).await {
+        eprintln!("{}: error: {}", path.display(), err);
+        ok = false;
+      }
+    }
+
+    for (index, size) in self.storage_buffers.iter() {
+      if let Err(err) = gfx.set_storage_buffer_size(*index, Some(*size)) {
+        eprintln!("error: {}", err);
+        ok = false;
+      }
+    }
+    for (index, size) in self.storage_textures.iter() {
+      if let Err(err) = gfx.set_storage_texture_size(*index, Some(*size)) {
+        eprintln!("error: {}", err);
+        ok = false;
+      }
+    }
+
+    for (index, path) in self.channels.iter() {
+      if let Err(err) = gfx.set_channel_from_file(*index, path, Default::default()) {
+        eprintln!("{}: error: {}", path.display(), err);
+        ok = false;
+      }
+    }
+
+    ok &= self.load_passes(gfx).await;
+
+    // uniforms and overrides keep their values when the passes are reloaded
+    for (name, values) in self.uniforms.iter() {
+      if let Err(err) = gfx.set_uniform(name, values) {
+        eprintln!("error: {}", err);
+        ok = false;
+      }
+    }
+    for (name, value) in self.overrides.iter() {
+      if let Err(err) = gfx.set_override(name, *value).await {
+        eprintln!("error: {}", err);
+        ok = false;
+      }
+    }
+
+    ok
+  }
+
+  // passes that fail to compile keep their previous pipeline
+  pub async fn load_passes(&self, gfx: &mut GfxState) -> bool {
+    let mut ok = true;
+    for (pass, path) in self.passes.iter() {
+      ok &= load_pass(gfx, *pass, path).await;
+    }
+    ok
+  }
+
+  // shader files and everything they include, canonicalized
+  pub fn dependencies(&self) -> Vec<PathBuf> {
+    self.passes.iter()
+      .flat_map(|(_, path)| match ShaderFile::read(path) {
+        Ok(shader) => shader.files,
+        Err(_) => vec![path.clone()],
+      })
+      .filter_map(|path| path.canonicalize().ok())
+      .collect()
+  }
+}
+
+#[derive(Debug)]
+pub struct ShaderFile {
+  pub source: String,
+  pub language: ShaderLanguage,
+  // every file that contributed to `source`, the shader itself first
+  pub files: Vec<PathBuf>,
+  // index into `files` and line number for every line of `source`
+  lines: Vec<(usize, u32)>,
+}
+
+impl ShaderFile {
+  // reads `path` expanding `#include "file"` lines, relative to the including file
+  pub fn read(path: &Path) -> std::io::Result<Self> {
+    let mut shader = ShaderFile {
+      source: String::new(),
+      language: language_from_path(path),
+      files: Vec::new(),
+      lines: Vec::new(),
+    };
+    shader.include(path, &mut Vec::new())?;
+    Ok(shader)
+  }
+
+  fn include(&mut self, path: &Path, stack: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let canonical = path.canonicalize()?;
+    if stack.contains(&canonical) {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("recursive include of {}", path.display())));
+    }
+
+    let source = std::fs::read_to_string(path)?;
+    let file_index = self.files.len();
+    self.files.push(path.to_path_buf());
+    stack.push(canonical);
+
+    for (line_index, line) in source.lines().enumerate() {
+      let include = line.trim()
+        .strip_prefix("#include")
+        .map(|rest| rest.trim().trim_matches('"'))
+        .filter(|name| !name.is_empty());
+
+      match include {
+        Some(name) => {
+          let included = path.parent().unwrap_or(Path::new(".")).join(name);
+          self.include(&included, stack)?;
+        },
+        None => {
+          self.source.push_str(line);
+          self.source.push('\n');
+          self.lines.push((file_index, line_index as u32 + 1));
+        },
+      }
+    }
+
+    stack.pop();
+    Ok(())
+  }
+
+  // prints messages as `path:line:column: type: message`, same fields as `ShaderCompilationInfo`
+  pub fn print_compilation_info(&self, info: &wgpu::CompilationInfo) {
+    for message in info.messages.iter() {
+      let message_type = match message.message_type {
+        wgpu::CompilationMessageType::Error => "error",
+        wgpu::CompilationMessageType::Warning => "warning",
+        wgpu::CompilationMessageType::Info => "info",
+      };
+
+      let location = message.location
+        .and_then(|location| Some((self.lines.get(location.line_number as usize - 1)?, location.line_position)));
+
+      match location {
+        Some(((file_index, line_number), line_position)) => eprintln!(
+          "{}:{}:{}: {}: {}",
+          self.files[*file_index].display(), line_number, line_position, message_type, message.message,
+        ),
+        None => eprintln!("{}: {}: {}", self.files[0].display(), message_type, message.message),
+      }
+    }
+  }
+}
+
+pub async fn load_pass(gfx: &mut GfxState, pass: PassTarget, path: &Path) -> bool {
+  let shader = match ShaderFile::read(path) {
+    Ok(shader) => shader,
+    Err(err) => {
+      eprintln!("{}: error: {}", path.display(), err);
+      return false;
+    },
+  };
+
+  match gfx.update_shader(pass, &shader.source, shader.language, &Default::default()).await {
+    Ok(()) => true,
+    Err(Error::Pipeline(messages)) => {
+      shader.print_compilation_info(&wgpu::CompilationInfo { messages });
+      false
+    },
+    Err(err) => {
+      eprintln!("{}: error: {}", path.display(), err);
+      false
+    },
+  }
+}
+
+pub fn language_from_path(path: &Path) -> ShaderLanguage {
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some("glsl") | Some("frag") | Some("fs") => ShaderLanguage::Glsl,
+    _ => ShaderLanguage::Wgsl,
+  }
+}
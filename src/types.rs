@@ -2,6 +2,9 @@
 use wasm_bindgen::prelude::*;
 use winit::window::WindowId;
 
+use crate::error::Error;
+use crate::gfx::{buffer_pass::PassTarget, channels::ChannelSamplerDesc, export::{ExportDesc, ExportFormat, ExportFrame}, parameters::{self, Parameter}, pipeline::{self, BlendMode, DrawDesc}, playback::Playback, shader::{EntryPoints, ShaderLanguage}, user_uniforms::UniformField};
+
 
 #[derive(Debug, Clone, Copy)]
 #[wasm_bindgen]
@@ -23,8 +26,30 @@ impl InstanceHandle {
 #[wasm_bindgen(typescript_custom_section)]
 const CustomTypes: &'static str = r#"
 interface IAppParams {
-  containerId: string;
-  callback: (handle: InstanceHandle) => void;
+  containerId?: string;
+  callback?: (handle: InstanceHandle) => void;
+}
+
+type TErrorCode =
+  | "WINDOW_CREATION_FAILED"
+  | "SURFACE_CREATION_FAILED"
+  | "SURFACE_UNSUPPORTED"
+  | "ADAPTER_UNAVAILABLE"
+  | "DEVICE_REQUEST_FAILED"
+  | "PIPELINE_CREATION_FAILED"
+  | "INVALID_PARAMETER"
+  | "CONTAINER_NOT_FOUND"
+  | "INSTANCE_NOT_FOUND"
+  | "IMAGE_DECODE_FAILED"
+  | "CAPTURE_FAILED"
+  | "EXPORT_IN_PROGRESS"
+  | "EXPORT_CANCELLED"
+  | "MESH_LOAD_FAILED"
+  | "UNSUPPORTED";
+
+// thrown for invalid parameters, otherwise passed to rejected promises and `onError`
+interface IShaderxError extends Error {
+  code: TErrorCode;
 }
 
 interface ICompilationMessage {
@@ -38,9 +63,143 @@ interface ICompilationMessage {
   };
 }
 
+type TPass = "image" | "bufferA" | "bufferB" | "bufferC" | "bufferD";
+
 interface IUpdateShaderParams {
   shaderSource: string;
+  // "glsl" expects shadertoy style `mainImage(out vec4, in vec2)`.
+  // wgsl without a `@vertex` entry point gets the built-in bindings and fullscreen vertex stage
+  // and provides `fs_main(input: FragmentInput)` or `mainImage(fragCoord: vec2<f32>) -> vec4<f32>`
+  language?: "wgsl" | "glsl";
+  pass?: TPass;
+  // default to the only entry point of the stage, or `vs_main` / `fs_main` when there are several
+  vertexEntryPoint?: string;
+  fragmentEntryPoint?: string;
   callback: (info: ShaderCompilationInfo) => void;
+  // called when the shader can't be applied for reasons other than its source
+  onError?: (error: IShaderxError) => void;
+}
+
+interface IChannelParams {
+  channel: 0 | 1 | 2 | 3;
+  data: Uint8Array;
+  filter?: "linear" | "nearest";
+  wrap?: "clamp" | "repeat" | "mirror";
+  flipY?: boolean;
+}
+
+// how the image pass is drawn, fields left out take their defaults: 3 vertices (the
+// fullscreen triangle), 1 instance, "triangles", "back" culling, "replace" blending, red.
+// the counts are also in `common_uniforms.vertex_count` and `instance_count`
+interface IDrawParams {
+  vertexCount?: number;
+  instanceCount?: number;
+  topology?: "points" | "lines" | "line-strip" | "triangles" | "triangle-strip";
+  cullMode?: "none" | "front" | "back";
+  blend?: "replace" | "alpha" | "premultiplied" | "additive";
+  // linear rgba
+  clearColor?: [number, number, number, number];
+}
+
+// a field of the uniform struct at @group(3) @binding(0), nested fields are named
+// `outer.inner` and array elements `name[i]`
+interface IUniformField {
+  name: string;
+  type: string;
+  offset: number;
+  size: number;
+  align: number;
+}
+
+type TUniformValue = number | boolean | ArrayLike<number>;
+
+// a uniform field or wgsl `override` annotated in a line comment, e.g.
+// `speed: f32, // @range(0, 10, 0.5) @default(2.5) @label("Speed")`,
+// `@color` goes on vec3 / vec4 fields and `@toggle` on scalars
+interface IParameter {
+  // the name taken by `setUniform` for uniforms and `setOverride` for overrides
+  name: string;
+  kind: "uniform" | "override";
+  type: string;
+  widget: "slider" | "color" | "toggle" | "input";
+  label?: string;
+  min?: number;
+  max?: number;
+  step?: number;
+  // a number for scalars, one value per component otherwise
+  default?: number | number[];
+}
+
+interface IParameterSchema {
+  parameters: IParameter[];
+}
+
+interface IExportFrame {
+  index: number;
+  frameCount: number;
+  time: number;
+  width: number;
+  height: number;
+  // png bytes, or rgba8 rows with the top row first
+  data: Uint8Array;
+}
+
+// renders `start` to `end` inclusive with a fixed timestep, independent of the canvas size.
+// buffer passes start empty, the canvas stops updating until the export is done
+interface IExportParams {
+  // 0 by default
+  start?: number;
+  end: number;
+  // 30 by default
+  fps?: number;
+  // the canvas size by default
+  width?: number;
+  height?: number;
+  format?: "png" | "rgba";
+  onFrame: (frame: IExportFrame) => void;
+  onProgress?: (done: number, total: number) => void;
+}
+
+// loops `start` to `end` with `end` left out, so the last frame leads back into the first.
+// quantized to 256 colors per frame, meant for short clips at low resolution
+interface IGifExportParams {
+  // 0 by default
+  start?: number;
+  end: number;
+  // 15 by default, at most 50
+  fps?: number;
+  // the canvas size scaled down to at most 320 pixels by default
+  width?: number;
+  height?: number;
+  onProgress?: (done: number, total: number) => void;
+}
+
+interface IPlaybackState {
+  time: number;
+  frame: number;
+  paused: boolean;
+  speed: number;
+  // set while the timestep is fixed
+  fixedFps?: number;
+  // both set while looping
+  loopStart?: number;
+  loopEnd?: number;
+}
+
+// the device is shared by every instance of an `EventHandler`, webgl2 is used where the
+// browser has no webgpu
+interface ICapabilities {
+  backend: "webgpu" | "webgl2" | "vulkan" | "metal" | "dx12" | "gl" | "empty";
+  // compute entry points, storage buffers and storage textures
+  compute: boolean;
+  limits: {
+    maxTextureDimension2D: number;
+    maxBufferSize: number;
+    maxStorageBuffersPerShaderStage: number;
+    maxStorageTexturesPerShaderStage: number;
+    maxComputeWorkgroupsPerDimension: number;
+    maxComputeInvocationsPerWorkgroup: number;
+  };
 }
 
 type TShaderCompilationInfoIteractorCallback = (message: ICompilationMessage) => void;
@@ -54,39 +213,93 @@ extern "C" {
   pub type TShaderCompilationInfoIteractorCallback;
   #[wasm_bindgen(typescript_type = "IUpdateShaderParams")]
   pub type IUpdateShaderParams;
+  #[wasm_bindgen(typescript_type = "TPass")]
+  pub type TPass;
+  #[wasm_bindgen(typescript_type = "IChannelParams")]
+  pub type IChannelParams;
+  #[wasm_bindgen(typescript_type = "IDrawParams")]
+  pub type IDrawParams;
+  #[wasm_bindgen(typescript_type = "TUniformValue")]
+  pub type TUniformValue;
+  #[wasm_bindgen(typescript_type = "IUniformField[]")]
+  pub type IUniformFieldArray;
+  #[wasm_bindgen(typescript_type = "IParameterSchema")]
+  pub type IParameterSchema;
+  #[wasm_bindgen(typescript_type = "IExportParams")]
+  pub type IExportParams;
+  #[wasm_bindgen(typescript_type = "IGifExportParams")]
+  pub type IGifExportParams;
 }
 
 #[wasm_bindgen]
 #[derive(Debug, Default)]
 pub struct ShaderCompilationInfo {
   messages: Vec<wgpu::CompilationMessage>,
+  uniforms: Vec<UniformField>,
+  parameters: Vec<Parameter>,
 }
 
 #[wasm_bindgen]
 impl ShaderCompilationInfo {
+  // like `Array.prototype.forEach`, stops at the first exception the callback throws and
+  // rethrows it
   #[wasm_bindgen(js_name = forEach)]
-  pub fn for_each(&self, callback: TShaderCompilationInfoIteractorCallback) {
+  pub fn for_each(&self, callback: TShaderCompilationInfoIteractorCallback) -> Result<(), JsValue> {
     let callback_js: JsValue = callback.into();
     if callback_js.is_undefined() || !callback_js.is_function() {
-      return;
+      return Ok(());
     }
     let callback_fn = js_sys::Function::from(callback_js);
-    self.messages.iter().for_each(|message| {
+    for message in self.messages.iter() {
       let js_message = compilation_message_to_js_value(message);
-      callback_fn.call1(&JsValue::NULL, &js_message).unwrap();
-    });
+      callback_fn.call1(&JsValue::NULL, &js_message)?;
+    }
+    Ok(())
   }
   
   #[wasm_bindgen(js_name = isEmpty)]
   pub fn is_empty(&self) -> bool {
     self.messages.is_empty()
   }
+
+  // user uniforms of the updated pass, empty if the shader didn't compile
+  #[wasm_bindgen(getter)]
+  pub fn uniforms(&self) -> IUniformFieldArray {
+    let uniforms: js_sys::Array = self.uniforms.iter().map(uniform_field_to_js_value).collect();
+    uniforms.unchecked_into()
+  }
+
+  // annotated parameters of the shader, empty if it didn't compile
+  #[wasm_bindgen(getter, js_name = parameterSchema)]
+  pub fn parameter_schema(&self) -> IParameterSchema {
+    js_sys::JSON::parse(&self.parameter_schema_json()).unwrap().unchecked_into()
+  }
+
+  // the same schema as a json string
+  #[wasm_bindgen(js_name = parameterSchemaJson)]
+  pub fn parameter_schema_json(&self) -> String {
+    parameters::schema_json(&self.parameters)
+  }
+}
+
+impl ShaderCompilationInfo {
+  pub fn with_uniforms(mut self, uniforms: Vec<UniformField>) -> Self {
+    self.uniforms = uniforms;
+    self
+  }
+
+  pub fn with_parameters(mut self, parameters: Vec<Parameter>) -> Self {
+    self.parameters = parameters;
+    self
+  }
 }
 
 impl From<wgpu::CompilationInfo> for ShaderCompilationInfo {
   fn from(info: wgpu::CompilationInfo) -> Self {
     ShaderCompilationInfo {
       messages: info.messages,
+      uniforms: Vec::new(),
+      parameters: Vec::new(),
     }
   }
 }
@@ -99,15 +312,262 @@ fn compilation_message_to_js_value(message: &wgpu::CompilationMessage) -> JsValu
     wgpu::CompilationMessageType::Info => "info",
   };
   js_sys::Reflect::set(&obj, &JsValue::from_str("message"), &JsValue::from_str(&message.message)).unwrap();
-  js_sys::Reflect::set(&obj, &JsValue::from_str("type"), &JsValue::from_str(&message_type)).unwrap();
-  if message.location.is_some() {
+  js_sys::Reflect::set(&obj, &JsValue::from_str("type"), &JsValue::from_str(message_type)).unwrap();
+  if let Some(location) = message.location {
     let loc = js_sys::Object::new();
-    js_sys::Reflect::set(&loc, &JsValue::from_str("lineNumber"), &JsValue::from_f64(message.location.unwrap().line_number as f64)).unwrap();
-    js_sys::Reflect::set(&loc, &JsValue::from_str("linePosition"), &JsValue::from_f64(message.location.unwrap().line_position as f64)).unwrap();
-    js_sys::Reflect::set(&loc, &JsValue::from_str("offset"), &JsValue::from_f64(message.location.unwrap().offset as f64)).unwrap();
-    js_sys::Reflect::set(&loc, &JsValue::from_str("length"), &JsValue::from_f64(message.location.unwrap().length as f64)).unwrap();
+    js_sys::Reflect::set(&loc, &JsValue::from_str("lineNumber"), &JsValue::from_f64(location.line_number as f64)).unwrap();
+    js_sys::Reflect::set(&loc, &JsValue::from_str("linePosition"), &JsValue::from_f64(location.line_position as f64)).unwrap();
+    js_sys::Reflect::set(&loc, &JsValue::from_str("offset"), &JsValue::from_f64(location.offset as f64)).unwrap();
+    js_sys::Reflect::set(&loc, &JsValue::from_str("length"), &JsValue::from_f64(location.length as f64)).unwrap();
     js_sys::Reflect::set(&obj, &JsValue::from_str("location"), &loc).unwrap();
   }
   obj.into()
 }
 
+pub fn export_frame_to_js_value(frame: &ExportFrame) -> JsValue {
+  let obj = js_sys::Object::new();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("index"), &JsValue::from_f64(frame.index as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("frameCount"), &JsValue::from_f64(frame.frame_count as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("time"), &JsValue::from_f64(frame.time as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("width"), &JsValue::from_f64(frame.width as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("height"), &JsValue::from_f64(frame.height as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("data"), &js_sys::Uint8Array::from(frame.data.as_slice())).unwrap();
+  obj.into()
+}
+
+pub fn playback_state_to_js_value(time: f32, frame: u32, playback: &Playback) -> JsValue {
+  let obj = js_sys::Object::new();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("time"), &JsValue::from_f64(time as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("frame"), &JsValue::from_f64(frame as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("paused"), &JsValue::from_bool(playback.paused)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("speed"), &JsValue::from_f64(playback.speed as f64)).unwrap();
+  if let Some(fps) = playback.fixed_fps() {
+    js_sys::Reflect::set(&obj, &JsValue::from_str("fixedFps"), &JsValue::from_f64(fps as f64)).unwrap();
+  }
+  if let Some((start, end)) = playback.loop_range {
+    js_sys::Reflect::set(&obj, &JsValue::from_str("loopStart"), &JsValue::from_f64(start as f64)).unwrap();
+    js_sys::Reflect::set(&obj, &JsValue::from_str("loopEnd"), &JsValue::from_f64(end as f64)).unwrap();
+  }
+  obj.into()
+}
+
+pub fn capabilities_to_js_value(backend: wgpu::Backend, compute: bool, limits: &wgpu::Limits) -> JsValue {
+  let backend = match backend {
+    wgpu::Backend::Empty => "empty",
+    wgpu::Backend::Vulkan => "vulkan",
+    wgpu::Backend::Metal => "metal",
+    wgpu::Backend::Dx12 => "dx12",
+    wgpu::Backend::Gl if cfg!(target_arch = "wasm32") => "webgl2",
+    wgpu::Backend::Gl => "gl",
+    wgpu::Backend::BrowserWebGpu => "webgpu",
+  };
+
+  let limits_obj = js_sys::Object::new();
+  js_sys::Reflect::set(&limits_obj, &JsValue::from_str("maxTextureDimension2D"), &JsValue::from_f64(limits.max_texture_dimension_2d as f64)).unwrap();
+  js_sys::Reflect::set(&limits_obj, &JsValue::from_str("maxBufferSize"), &JsValue::from_f64(limits.max_buffer_size as f64)).unwrap();
+  js_sys::Reflect::set(&limits_obj, &JsValue::from_str("maxStorageBuffersPerShaderStage"), &JsValue::from_f64(limits.max_storage_buffers_per_shader_stage as f64)).unwrap();
+  js_sys::Reflect::set(&limits_obj, &JsValue::from_str("maxStorageTexturesPerShaderStage"), &JsValue::from_f64(limits.max_storage_textures_per_shader_stage as f64)).unwrap();
+  js_sys::Reflect::set(&limits_obj, &JsValue::from_str("maxComputeWorkgroupsPerDimension"), &JsValue::from_f64(limits.max_compute_workgroups_per_dimension as f64)).unwrap();
+  js_sys::Reflect::set(&limits_obj, &JsValue::from_str("maxComputeInvocationsPerWorkgroup"), &JsValue::from_f64(limits.max_compute_invocations_per_workgroup as f64)).unwrap();
+
+  let obj = js_sys::Object::new();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("backend"), &JsValue::from_str(backend)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("compute"), &JsValue::from_bool(compute)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("limits"), &limits_obj).unwrap();
+  obj.into()
+}
+
+fn uniform_field_to_js_value(field: &UniformField) -> JsValue {
+  let obj = js_sys::Object::new();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&field.name)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("type"), &JsValue::from_str(&field.type_name())).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("offset"), &JsValue::from_f64(field.offset as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("size"), &JsValue::from_f64(field.size() as f64)).unwrap();
+  js_sys::Reflect::set(&obj, &JsValue::from_str("align"), &JsValue::from_f64(field.align() as f64)).unwrap();
+  obj.into()
+}
+
+// reads `key` from a params object, `undefined` and `null` count as missing
+pub fn get_param(params: &JsValue, key: &str) -> Result<Option<JsValue>, Error> {
+  let value = js_sys::Reflect::get(params, &JsValue::from_str(key))
+    .map_err(|_| Error::InvalidParameter(format!("expected an object with `{}`", key)))?;
+
+  if value.is_undefined() || value.is_null() {
+    Ok(None)
+  } else {
+    Ok(Some(value))
+  }
+}
+
+pub fn require<T>(value: Option<T>, key: &str) -> Result<T, Error> {
+  value.ok_or_else(|| Error::InvalidParameter(format!("`{}` is required", key)))
+}
+
+pub fn get_string(params: &JsValue, key: &str) -> Result<Option<String>, Error> {
+  get_param(params, key)?
+    .map(|value| value.as_string().ok_or_else(|| Error::InvalidParameter(format!("`{}` must be a string", key))))
+    .transpose()
+}
+
+pub fn get_number(params: &JsValue, key: &str) -> Result<Option<f64>, Error> {
+  get_param(params, key)?
+    .map(|value| value.as_f64().ok_or_else(|| Error::InvalidParameter(format!("`{}` must be a number", key))))
+    .transpose()
+}
+
+pub fn get_bool(params: &JsValue, key: &str) -> Result<Option<bool>, Error> {
+  get_param(params, key)?
+    .map(|value| value.as_bool().ok_or_else(|| Error::InvalidParameter(format!("`{}` must be a boolean", key))))
+    .transpose()
+}
+
+pub fn get_function(params: &JsValue, key: &str) -> Result<Option<js_sys::Function>, Error> {
+  get_param(params, key)?
+    .map(|value| value.dyn_into::<js_sys::Function>().map_err(|_| Error::InvalidParameter(format!("`{}` must be a function", key))))
+    .transpose()
+}
+
+// missing passes default to the image pass
+pub fn pass_target_from_js(value: &JsValue) -> Result<PassTarget, Error> {
+  if value.is_undefined() || value.is_null() {
+    return Ok(PassTarget::Image);
+  }
+
+  value.as_string()
+    .and_then(|name| PassTarget::from_name(&name))
+    .ok_or_else(|| Error::InvalidParameter(format!("unknown pass: {:?}", value)))
+}
+
+pub fn shader_language_from_js(value: &JsValue) -> Result<ShaderLanguage, Error> {
+  if value.is_undefined() || value.is_null() {
+    return Ok(ShaderLanguage::default());
+  }
+
+  value.as_string()
+    .and_then(|name| ShaderLanguage::from_name(&name))
+    .ok_or_else(|| Error::InvalidParameter(format!("unknown language: {:?}", value)))
+}
+
+pub fn uniform_values_from_js(value: &JsValue) -> Result<Vec<f64>, Error> {
+  if let Some(number) = value.as_f64() {
+    return Ok(vec![number]);
+  }
+
+  if let Some(flag) = value.as_bool() {
+    return Ok(vec![if flag { 1.0 } else { 0.0 }]);
+  }
+
+  if !value.is_object() {
+    return Err(Error::InvalidParameter(String::from("uniform values must be a number, boolean or array of numbers")));
+  }
+
+  // arrays and typed arrays
+  js_sys::Array::from(value).iter()
+    .map(|item| item.as_f64().ok_or_else(|| Error::InvalidParameter(String::from("uniform arrays must only hold numbers"))))
+    .collect()
+}
+
+pub fn entry_points_from_js(params: &JsValue) -> Result<EntryPoints, Error> {
+  Ok(EntryPoints {
+    vertex: get_string(params, "vertexEntryPoint")?,
+    fragment: get_string(params, "fragmentEntryPoint")?,
+  })
+}
+
+fn export_size_from_js(params: &JsValue) -> Result<Option<(u32, u32)>, Error> {
+  match (get_number(params, "width")?, get_number(params, "height")?) {
+    (None, None) => Ok(None),
+    (Some(width), Some(height)) if width.fract() == 0.0 && height.fract() == 0.0 && width >= 1.0 && height >= 1.0 => Ok(Some((width as u32, height as u32))),
+    (Some(_), Some(_)) => Err(Error::InvalidParameter(String::from("`width` and `height` must be positive integers"))),
+    _ => Err(Error::InvalidParameter(String::from("export needs both `width` and `height`"))),
+  }
+}
+
+pub fn export_desc_from_js(params: &JsValue) -> Result<(ExportDesc, ExportFormat), Error> {
+  let size = export_size_from_js(params)?;
+  let format = match get_string(params, "format")? {
+    Some(name) => ExportFormat::from_name(&name).ok_or_else(|| Error::InvalidParameter(format!("unknown export format: {}", name)))?,
+    None => ExportFormat::default(),
+  };
+
+  let desc = ExportDesc {
+    start: get_number(params, "start")?.unwrap_or(0.0) as f32,
+    end: require(get_number(params, "end")?, "end")? as f32,
+    fps: get_number(params, "fps")?.unwrap_or(30.0) as f32,
+    size,
+  };
+  Ok((desc, format))
+}
+
+pub fn gif_desc_from_js(params: &JsValue) -> Result<ExportDesc, Error> {
+  Ok(ExportDesc {
+    start: get_number(params, "start")?.unwrap_or(0.0) as f32,
+    end: require(get_number(params, "end")?, "end")? as f32,
+    fps: get_number(params, "fps")?.unwrap_or(15.0) as f32,
+    size: export_size_from_js(params)?,
+  })
+}
+
+pub fn draw_desc_from_js(params: &JsValue) -> Result<DrawDesc, Error> {
+  let mut draw = DrawDesc::default();
+
+  let count = |key: &str| -> Result<Option<u32>, Error> {
+    match get_number(params, key)? {
+      Some(count) if count.fract() != 0.0 || count < 1.0 || count > u32::MAX as f64 => Err(Error::InvalidParameter(format!("`{}` must be a positive integer, got {}", key, count))),
+      count => Ok(count.map(|count| count as u32)),
+    }
+  };
+  if let Some(vertex_count) = count("vertexCount")? {
+    draw.vertex_count = vertex_count;
+  }
+  if let Some(instance_count) = count("instanceCount")? {
+    draw.instance_count = instance_count;
+  }
+
+  if let Some(topology) = get_string(params, "topology")? {
+    draw.topology = pipeline::topology_from_name(&topology).ok_or_else(|| Error::InvalidParameter(format!("unknown topology: {}", topology)))?;
+  }
+  if let Some(cull_mode) = get_string(params, "cullMode")? {
+    draw.cull_mode = pipeline::cull_mode_from_name(&cull_mode).ok_or_else(|| Error::InvalidParameter(format!("unknown cull mode: {}", cull_mode)))?;
+  }
+  if let Some(blend) = get_string(params, "blend")? {
+    draw.blend = BlendMode::from_name(&blend).ok_or_else(|| Error::InvalidParameter(format!("unknown blend mode: {}", blend)))?;
+  }
+
+  if let Some(clear_color) = get_param(params, "clearColor")? {
+    let [r, g, b, a] = uniform_values_from_js(&clear_color)?[..] else {
+      return Err(Error::InvalidParameter(String::from("`clearColor` must hold 4 numbers")));
+    };
+    draw.clear_color = wgpu::Color { r, g, b, a };
+  }
+
+  draw.validate()?;
+  Ok(draw)
+}
+
+pub fn channel_sampler_from_js(params: &JsValue) -> Result<ChannelSamplerDesc, Error> {
+  let mut sampler = ChannelSamplerDesc::default();
+
+  if let Some(filter) = get_string(params, "filter")? {
+    sampler.filter = match filter.as_str() {
+      "nearest" => wgpu::FilterMode::Nearest,
+      "linear" => wgpu::FilterMode::Linear,
+      _ => return Err(Error::InvalidParameter(format!("unknown filter: {}", filter))),
+    };
+  }
+
+  if let Some(wrap) = get_string(params, "wrap")? {
+    sampler.address_mode = match wrap.as_str() {
+      "clamp" => wgpu::AddressMode::ClampToEdge,
+      "mirror" => wgpu::AddressMode::MirrorRepeat,
+      "repeat" => wgpu::AddressMode::Repeat,
+      _ => return Err(Error::InvalidParameter(format!("unknown wrap mode: {}", wrap))),
+    };
+  }
+
+  if let Some(flip_y) = get_bool(params, "flipY")? {
+    sampler.flip_y = flip_y;
+  }
+
+  Ok(sampler)
+}